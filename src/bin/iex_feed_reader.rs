@@ -1,23 +1,104 @@
-use bytes::BytesMut;
-use iex_feed::packetprocessor::*;
-use pcap_parser::data::PacketData;
-use pcap_parser::traits::PcapReaderIterator;
-use pcap_parser::*;
+use iex_feed::error::ReaderError;
+use iex_feed::feedreader::IexFeedReader;
+use iex_feed::feedwriter::{CaptureFormat, IexFeedWriter};
+use iex_feed::iexdata::Symbol;
+use iex_feed::packetfilter::{MessageFilter, PacketFilter, SequenceMode};
 
 use std::env;
 use std::fs::File;
+use std::io::BufWriter;
 
-use clap::{command, Arg, ArgAction};
+use chrono::{DateTime, Utc};
+use clap::{command, value_parser, Arg, ArgAction, ArgMatches, Command};
 
-const FRAME_HEADER_LENGTH: usize = 42;
+// Message type given as its character, e.g. Q, or as hexadecimal, e.g. 0x51
+fn parse_message_type(value: &str) -> Result<u8, String> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16).map_err(|e| e.to_string()),
+        None if value.len() == 1 && value.is_ascii() => Ok(value.as_bytes()[0]),
+        None => Err(format!("invalid message type {:?}", value)),
+    }
+}
 
-fn main() {
-    env::set_var(
-        "RUST_BACKTRA
-    CE",
-        "1",
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| e.to_string())
+}
+
+// Write the packets of the input matching the filter to the output capture.
+// Packets that cannot be decoded are skipped; other failures stop the filter.
+fn filter(matches: &ArgMatches) -> Result<(), String> {
+    let input = matches.get_one::<String>("file").unwrap();
+    let output = matches.get_one::<String>("output").unwrap();
+    let mut message_filter = MessageFilter::new().send_time_window(
+        matches.get_one::<DateTime<Utc>>("start").copied(),
+        matches.get_one::<DateTime<Utc>>("end").copied(),
     );
-    let matches = command!() // requires `cargo` feature
+    if let Some(symbols) = matches.get_many::<Symbol>("symbols") {
+        message_filter = message_filter.symbols(symbols.copied());
+    }
+    if let Some(msg_types) = matches.get_many::<u8>("types") {
+        message_filter = message_filter.message_types(msg_types.copied());
+    }
+    let mode = if matches.get_flag("keep-sequence") {
+        SequenceMode::Original
+    } else {
+        SequenceMode::Resequenced
+    };
+    let mut packet_filter = PacketFilter::new(message_filter, mode);
+
+    let reader = IexFeedReader::open(input)
+        .map_err(|e| format!("Cannot read the selected file: {}", e))?;
+    let file = File::create(output)
+        .map(BufWriter::new)
+        .map_err(|e| format!("Cannot create {}: {}", output, e))?;
+    let format = CaptureFormat::from_path(output);
+    let writer = match mode {
+        // Readers of the output must not take it for the original feed
+        SequenceMode::Resequenced => IexFeedWriter::new_with_comment(
+            file,
+            format,
+            &format!("IEX-TP segments re-sequenced by iex_feed_reader filter from {}", input),
+        ),
+        SequenceMode::Original => IexFeedWriter::new(file, format),
+    };
+    let mut writer = writer.map_err(|e| format!("Cannot write {}: {}", output, e))?;
+
+    let mut num_packets = 0;
+    for result in reader {
+        let packet = match result {
+            Ok(packet) => packet,
+            Err(e @ ReaderError::Decode { .. }) => {
+                eprintln!("{}", e);
+                continue;
+            }
+            Err(e) => return Err(format!("Cannot read {}: {}", input, e)),
+        };
+        num_packets += 1;
+        packet_filter
+            .apply(packet)
+            .map_err(|e| e.into())
+            .and_then(|packet| match packet {
+                Some(packet) => writer.write_packet(&packet),
+                None => Ok(()),
+            })
+            .map_err(|e| format!("Cannot write {}: {}", output, e))?;
+    }
+    let packets_written = writer.packets_written();
+    writer
+        .into_inner()
+        .map_err(|e| format!("Cannot write {}: {}", output, e))?;
+    println!("num_packets: {}", num_packets);
+    println!("packets_written: {}", packets_written);
+    if mode == SequenceMode::Resequenced {
+        println!("sequence numbers and stream offsets of {} are re-sequenced", output);
+    }
+    Ok(())
+}
+
+fn cli() -> Command<'static> {
+    command!() // requires `cargo` feature
         .version("0.1.0")
         .author("Francesco Fucci")
         .about("Can read IEX pcap feeds")
@@ -28,79 +109,153 @@ fn main() {
                 .action(ArgAction::Append)
                 .help("PCAP file to be read"),
         )
-        .get_matches();
+        .subcommand(
+            Command::new("filter")
+                .about("Writes the packets matching symbols, message types and a send time window to a new capture")
+                .after_help(
+                    "Matching messages are re-packed in new segments whose sequence numbers and \
+                     stream offsets start again from 1 and 0; a pcapng output is marked with a \
+                     section comment. With --keep-sequence the packets holding a matching message \
+                     are written with all their messages and their original header fields instead; \
+                     the messages are re-encoded, so bytes past the fields of a known message type \
+                     are not kept.",
+                )
+                .arg(
+                    Arg::new("file")
+                        .short('f')
+                        .long("file")
+                        .action(ArgAction::Set)
+                        .required(true)
+                        .help("Capture to read"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .action(ArgAction::Set)
+                        .required(true)
+                        .help("Capture to write, legacy pcap if it ends with .pcap, pcapng otherwise"),
+                )
+                .arg(
+                    Arg::new("symbols")
+                        .short('s')
+                        .long("symbols")
+                        .action(ArgAction::Append)
+                        .value_delimiter(',')
+                        .value_parser(value_parser!(Symbol))
+                        .help("Comma separated symbols, messages without symbol do not match"),
+                )
+                .arg(
+                    Arg::new("types")
+                        .short('t')
+                        .long("types")
+                        .action(ArgAction::Append)
+                        .value_delimiter(',')
+                        .value_parser(parse_message_type)
+                        .help("Comma separated message types, as characters (Q,T) or hexadecimal (0x51)"),
+                )
+                .arg(
+                    Arg::new("start")
+                        .long("start")
+                        .action(ArgAction::Set)
+                        .value_parser(parse_time)
+                        .help("Keep packets sent at or after this RFC 3339 time"),
+                )
+                .arg(
+                    Arg::new("end")
+                        .long("end")
+                        .action(ArgAction::Set)
+                        .value_parser(parse_time)
+                        .help("Keep packets sent before this RFC 3339 time"),
+                )
+                .arg(
+                    Arg::new("keep-sequence")
+                        .long("keep-sequence")
+                        .action(ArgAction::SetTrue)
+                        .help("Keep the packets holding a matching message with their original sequence numbers"),
+                ),
+        )
+}
 
-    let default_path = &"./test/20180127_IEXTP1_TOPS1.6.pcap".to_string();
-    let path = matches.get_one::<String>("file").unwrap_or(default_path);
-    let result_file = File::open(path);
-    if result_file.is_err() {
-        println!("Cannot open the selected file: check the file path.");
+fn main() {
+    env::set_var(
+        "RUST_BACKTRA
+    CE",
+        "1",
+    );
+    // Decoding traces go to stderr, enabled with RUST_LOG=debug or RUST_LOG=trace
+    env_logger::init();
+    let matches = cli().get_matches();
+
+    if let Some(matches) = matches.subcommand_matches("filter") {
+        if let Err(e) = filter(matches) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
         return;
     }
-    let mut num_blocks = 0;
-    let mut reader = PcapNGReader::new(65536, result_file.unwrap()).expect("PcapNGReader");
-    let mut if_linktypes = Vec::new();
-    let packet_processor = IEXPacketProcessor {};
-    loop {
-        match reader.next() {
-            Ok((offset, block)) => {
-                println!("got new block");
-                num_blocks += 1;
 
-                match block {
-                    PcapBlockOwned::NG(Block::SectionHeader(ref _shb)) => {
-                        // starting a new section, clear known interfaces
-                        println!("data");
-                        if_linktypes = Vec::new();
-                    }
-                    PcapBlockOwned::NG(Block::InterfaceDescription(ref idb)) => {
-                        if_linktypes.push(idb.linktype);
-                        println!("linktype {:?}", if_linktypes);
-                    }
-                    PcapBlockOwned::NG(Block::EnhancedPacket(ref epb)) => {
-                        assert!((epb.if_id as usize) < if_linktypes.len());
-                        let linktype = if_linktypes[epb.if_id as usize];
-                        println!("=====> block data : epb.caplen {0}", epb.caplen);
-                        let res = pcap_parser::data::get_packetdata(
-                            epb.data,
-                            linktype,
-                            epb.caplen as usize,
-                        );
-                        packet_processor.process_packet_data(res, FRAME_HEADER_LENGTH);
-                    }
-                    PcapBlockOwned::NG(Block::SimplePacket(ref spb)) => {
-                        assert!(if_linktypes.len() > 0);
-                        let linktype = if_linktypes[0];
-                        let blen = (spb.block_len1 - 16) as usize;
-                        {
-                            let res = pcap_parser::data::get_packetdata(spb.data, linktype, blen);
-                            if let Some(packet) = res.clone() {
-                                match packet {
-                                    PacketData::L2(curr) => {
-                                        let x = BytesMut::from(curr);
-                                        println!("values : {:?}", x);
-                                    }
-                                    PacketData::L3(_, _)
-                                    | PacketData::L4(_, _)
-                                    | PacketData::Unsupported(_) => todo!(),
-                                }
-                            }
-                        }
-                    }
-                    PcapBlockOwned::NG(_) => {
-                        // can be statistics (ISB), name resolution (NRB), etc.
-                        eprintln!("unsupported block");
-                    }
-                    PcapBlockOwned::Legacy(_) | PcapBlockOwned::LegacyHeader(_) => unreachable!(),
-                }
-                reader.consume(offset);
+    let default_path = &"./test/20180127_IEXTP1_TOPS1.6.pcap".to_string();
+    let path = matches.get_one::<String>("file").unwrap_or(default_path);
+    // gzip and zstd compressed captures, pcapng and legacy pcap files are all detected
+    let reader = match IexFeedReader::open(path) {
+        Ok(reader) => reader,
+        Err(e) => {
+            println!("Cannot read the selected file: {}", e);
+            return;
+        }
+    };
+    let mut num_packets = 0;
+    for result in reader {
+        match result {
+            Ok(packet) => {
+                num_packets += 1;
+                println!("{:?}", packet);
             }
-            Err(PcapError::Eof) => break,
-            Err(PcapError::Incomplete) => {
-                reader.refill().unwrap();
+            Err(e @ ReaderError::Decode { .. }) => eprintln!("{}", e),
+            Err(e) => {
+                eprintln!("Cannot read {}: {}", path, e);
+                std::process::exit(1);
             }
-            Err(e) => panic!("error while reading: {:?}", e),
         }
     }
-    println!("num_blocks: {}", num_blocks);
+    println!("num_packets: {}", num_packets);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_fails_on_truncated_capture() {
+        let dir = env::temp_dir();
+        let id = std::process::id();
+        let input = dir.join(format!("iex_feed_reader_{}_truncated.pcap", id));
+        let output = dir.join(format!("iex_feed_reader_{}_filtered.pcap", id));
+        // Legacy pcap header and a record announcing 100 bytes of which 10 are present
+        let mut pcap = vec![];
+        for word in [0xa1b2c3d4, 0x00040002, 0, 0, 65535, 1, 0, 0, 100, 100u32] {
+            pcap.extend_from_slice(&word.to_le_bytes());
+        }
+        pcap.extend_from_slice(&[0; 10]);
+        std::fs::write(&input, pcap).unwrap();
+
+        let matches = cli().get_matches_from([
+            "iex_feed_reader",
+            "filter",
+            "--file",
+            input.to_str().unwrap(),
+            "--output",
+            output.to_str().unwrap(),
+        ]);
+        let result = filter(matches.subcommand_matches("filter").unwrap());
+        std::fs::remove_file(&input).unwrap();
+        let _ = std::fs::remove_file(&output);
+        let error = result.unwrap_err();
+        assert!(
+            error.starts_with(&format!("Cannot read {}: ", input.display())),
+            "unexpected error {}",
+            error
+        );
+    }
 }
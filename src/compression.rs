@@ -0,0 +1,146 @@
+use std::io::{self, BufReader, Cursor, Read};
+
+use flate2::bufread::MultiGzDecoder;
+
+// Longest magic number looked for
+const MAGIC_LENGTH: usize = 4;
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+#[cfg(feature = "zstd")]
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Compression {
+    None,
+    Gzip,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+/// Detect the compression of a stream from its magic bytes
+pub fn detect_compression(magic: &[u8]) -> Compression {
+    if magic.starts_with(&GZIP_MAGIC) {
+        return Compression::Gzip;
+    }
+    #[cfg(feature = "zstd")]
+    if magic.starts_with(&ZSTD_MAGIC) {
+        return Compression::Zstd;
+    }
+    Compression::None
+}
+
+// Stream whose first bytes were read ahead. A read returns the bytes read
+// ahead followed by new ones, so a caller reading once gets more than the magic.
+struct ReadAhead<R> {
+    bytes: Cursor<Vec<u8>>,
+    reader: R,
+}
+
+impl<R: Read> Read for ReadAhead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let length = self.bytes.read(buf)?;
+        if length == 0 {
+            return self.reader.read(buf);
+        }
+        // A failed read is left for the next call to report
+        Ok(length + self.reader.read(&mut buf[length..]).unwrap_or(0))
+    }
+}
+
+/// Wrap `reader` in a streaming decompressor when its content is gzip (or zstd)
+/// compressed; uncompressed input is returned as is. Nothing is written to disk.
+pub fn decompressed_reader<'a, R: Read + 'a>(mut reader: R) -> io::Result<Box<dyn Read + 'a>> {
+    // A pipe can return the magic bytes over several reads
+    let mut magic = Vec::with_capacity(MAGIC_LENGTH);
+    reader
+        .by_ref()
+        .take(MAGIC_LENGTH as u64)
+        .read_to_end(&mut magic)?;
+    let compression = detect_compression(&magic);
+    // Put the magic bytes back in front of the rest of the stream
+    let reader = BufReader::new(ReadAhead {
+        bytes: Cursor::new(magic),
+        reader,
+    });
+    Ok(match compression {
+        // IEX HIST files can be made of several gzip members
+        Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(reader)?),
+        Compression::None => Box::new(reader),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+
+    use super::*;
+
+    const CONTENT: &[u8] = b"\xd4\xc3\xb2\xa1 some pcap bytes";
+
+    fn read_all(input: &[u8]) -> Vec<u8> {
+        let mut output = vec![];
+        decompressed_reader(input)
+            .unwrap()
+            .read_to_end(&mut output)
+            .unwrap();
+        output
+    }
+
+    #[test]
+    fn test_plain_input_is_unchanged() {
+        assert_eq!(detect_compression(CONTENT), Compression::None);
+        assert_eq!(read_all(CONTENT), CONTENT);
+    }
+
+    #[test]
+    fn test_gzip_input_is_decompressed() {
+        let mut encoder = GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(CONTENT).unwrap();
+        let mut compressed = encoder.finish().unwrap();
+        // A second gzip member is read as well
+        let second = compressed.clone();
+        compressed.extend(second);
+        assert_eq!(detect_compression(&compressed), Compression::Gzip);
+        assert_eq!(read_all(&compressed), [CONTENT, CONTENT].concat());
+    }
+
+    // Returns a single byte per read, as a slow pipe can
+    struct ByteReader<'a>(&'a [u8]);
+
+    impl Read for ByteReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let length = self.0.len().min(buf.len()).min(1);
+            buf[..length].copy_from_slice(&self.0[..length]);
+            self.0 = &self.0[length..];
+            Ok(length)
+        }
+    }
+
+    #[test]
+    fn test_magic_bytes_over_several_reads() {
+        let mut encoder = GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(CONTENT).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut output = vec![];
+        decompressed_reader(ByteReader(&compressed))
+            .unwrap()
+            .read_to_end(&mut output)
+            .unwrap();
+        assert_eq!(output, CONTENT);
+
+        // Inputs shorter than any magic number are read as they are
+        assert_eq!(read_all(&[0x1f]), [0x1f]);
+        assert_eq!(read_all(&[]), []);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_input_is_decompressed() {
+        let compressed = zstd::stream::encode_all(CONTENT, 0).unwrap();
+        assert_eq!(detect_compression(&compressed), Compression::Zstd);
+        assert_eq!(read_all(&compressed), CONTENT);
+    }
+}
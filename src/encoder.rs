@@ -0,0 +1,552 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::error::EncodeError;
+use crate::iexdata::*;
+use crate::packetprocessor::{
+    IEXPacket, IEX_TP_HEADER_LENGTH, IEX_TP_VERSION, MESSAGE_LENGTH_SIZE,
+};
+
+fn serialize<T: Serialize>(message: &T) -> Result<Vec<u8>, EncodeError> {
+    bincode::serialize(message).map_err(|e| EncodeError::Serialize(e.to_string()))
+}
+
+// Same rules as the decoder, which keeps messages of another feed as unknown
+fn is_carried_by(message: &IEXMessage, protocol: IEXProtocol) -> bool {
+    match message {
+        IEXMessage::QuoteUpdate(_) => protocol == IEXProtocol::Tops,
+        IEXMessage::PriceLevelUpdateBuy(_) | IEXMessage::PriceLevelUpdateSell(_) => {
+            protocol == IEXProtocol::Deep
+        }
+        IEXMessage::SecurityEvent(_) => {
+            matches!(protocol, IEXProtocol::Deep | IEXProtocol::DeepPlus)
+        }
+        IEXMessage::AddOrder(_)
+        | IEXMessage::OrderModify(_)
+        | IEXMessage::OrderDelete(_)
+        | IEXMessage::OrderExecuted(_) => protocol == IEXProtocol::DeepPlus,
+        _ => true,
+    }
+}
+
+/// Wire bytes of a message, starting with its type and without the length prefix.
+/// Known messages are encoded from their fields only, see `encode_packet`.
+pub fn encode_message(message: &IEXMessage) -> Result<Vec<u8>, EncodeError> {
+    match message {
+        IEXMessage::AuctionInformation(message) => serialize(message),
+        IEXMessage::SecurityDirectory(message) => serialize(message),
+        IEXMessage::TradingStatus(message) => serialize(message),
+        IEXMessage::RetailLiquidityIndicator(message) => serialize(message),
+        IEXMessage::ShortSalePriceTestStatus(message) => serialize(message),
+        IEXMessage::QuoteUpdate(message) => serialize(message),
+        IEXMessage::TradeReport(message) => serialize(message),
+        IEXMessage::SystemEvent(message) => serialize(message),
+        IEXMessage::TradeBreak(message) => serialize(message),
+        IEXMessage::OfficialPrice(message) => serialize(message),
+        IEXMessage::OperationalHalt(message) => serialize(message),
+        IEXMessage::PriceLevelUpdateBuy(message) => serialize(message),
+        IEXMessage::PriceLevelUpdateSell(message) => serialize(message),
+        IEXMessage::SecurityEvent(message) => serialize(message),
+        IEXMessage::AddOrder(message) => serialize(message),
+        IEXMessage::OrderModify(message) => serialize(message),
+        IEXMessage::OrderDelete(message) => serialize(message),
+        IEXMessage::OrderExecuted(message) => serialize(message),
+        IEXMessage::Unknown { bytes, .. } => Ok(bytes.clone()),
+    }
+}
+
+/// Builds the IEX-TP segments of one feed session from typed messages.
+///
+/// Messages are pushed one by one, then `finish` emits the segment with its
+/// header: payload length, message count, first message sequence number and
+/// stream offset are computed, and carried over to the next segment. Finishing
+/// an empty segment gives a heartbeat.
+#[derive(Debug)]
+pub struct IexTpSegmentBuilder {
+    protocol: IEXProtocol,
+    channel_id: u32,
+    session_id: u32,
+    next_seq_number: u64,
+    stream_offset: u64,
+    max_payload_length: usize,
+    payload: Vec<u8>,
+    message_count: u16,
+}
+
+impl IexTpSegmentBuilder {
+    /// Builder of a new session, starting at sequence number 1 and stream offset 0
+    pub fn new(protocol: IEXProtocol, session_id: u32) -> IexTpSegmentBuilder {
+        IexTpSegmentBuilder {
+            protocol,
+            channel_id: 1,
+            session_id,
+            next_seq_number: 1,
+            stream_offset: 0,
+            max_payload_length: u16::MAX as usize,
+            payload: vec![],
+            message_count: 0,
+        }
+    }
+
+    /// Builder continuing the session of `header`, the next segment starts where `header` does
+    pub fn from_header(header: &IEXHeader) -> Result<IexTpSegmentBuilder, EncodeError> {
+        let protocol = header
+            .protocol()
+            .ok_or(EncodeError::UnsupportedProtocol(header.protocol_id))?;
+        Ok(IexTpSegmentBuilder::new(protocol, header.session_id)
+            .channel_id(header.channel_id)
+            .first_message_seq_number(header.first_message_seq_number)
+            .stream_offset(header.stream_offset))
+    }
+
+    pub fn channel_id(mut self, channel_id: u32) -> IexTpSegmentBuilder {
+        self.channel_id = channel_id;
+        self
+    }
+
+    pub fn first_message_seq_number(mut self, seq_number: u64) -> IexTpSegmentBuilder {
+        self.next_seq_number = seq_number;
+        self
+    }
+
+    pub fn stream_offset(mut self, stream_offset: u64) -> IexTpSegmentBuilder {
+        self.stream_offset = stream_offset;
+        self
+    }
+
+    /// Limit the payload of the segments, e.g. to stay below the network MTU
+    pub fn max_payload_length(mut self, max_payload_length: u16) -> IexTpSegmentBuilder {
+        self.max_payload_length = max_payload_length as usize;
+        self
+    }
+
+    pub fn protocol(&self) -> IEXProtocol {
+        self.protocol
+    }
+
+    /// Sequence number of the first message of the segment being built
+    pub fn next_seq_number(&self) -> u64 {
+        self.next_seq_number
+    }
+
+    pub fn next_stream_offset(&self) -> u64 {
+        self.stream_offset
+    }
+
+    pub fn message_count(&self) -> u16 {
+        self.message_count
+    }
+
+    pub fn payload_length(&self) -> usize {
+        self.payload.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.message_count == 0
+    }
+
+    /// Drop the messages of the segment being built; the next segment starts
+    /// where the last finished one ended
+    pub fn clear(&mut self) {
+        self.payload.clear();
+        self.message_count = 0;
+    }
+
+    /// Append a message to the segment being built.
+    /// Returns `SegmentFull` when it does not fit, the segment is left unchanged.
+    pub fn push(&mut self, message: &IEXMessage) -> Result<(), EncodeError> {
+        if !is_carried_by(message, self.protocol) {
+            return Err(EncodeError::UnexpectedMessage {
+                msg_type: message.msg_type(),
+                protocol: self.protocol,
+            });
+        }
+        let bytes = encode_message(message)?;
+        let length =
+            u16::try_from(bytes.len()).map_err(|_| EncodeError::MessageTooLong(bytes.len()))?;
+        if self.message_count == u16::MAX
+            || self.payload.len() + MESSAGE_LENGTH_SIZE + bytes.len() > self.max_payload_length
+        {
+            return Err(EncodeError::SegmentFull);
+        }
+        self.payload.extend_from_slice(&length.to_le_bytes());
+        self.payload.extend_from_slice(&bytes);
+        self.message_count += 1;
+        Ok(())
+    }
+
+    /// Header of the segment being built
+    pub fn header(&self, send_time: DateTime<Utc>) -> IEXHeader {
+        IEXHeader {
+            version: IEX_TP_VERSION,
+            __reserved: 0,
+            protocol_id: self.protocol as u16,
+            channel_id: self.channel_id,
+            session_id: self.session_id,
+            payload_length: self.payload.len() as u16,
+            message_count: self.message_count,
+            stream_offset: self.stream_offset,
+            first_message_seq_number: self.next_seq_number,
+            send_time,
+        }
+    }
+
+    /// Emit the segment and start the next one after it
+    pub fn finish(&mut self, send_time: DateTime<Utc>) -> Result<Vec<u8>, EncodeError> {
+        let mut segment = Vec::with_capacity(IEX_TP_HEADER_LENGTH + self.payload.len());
+        segment.extend_from_slice(&serialize(&self.header(send_time))?);
+        segment.extend_from_slice(&self.payload);
+        self.next_seq_number += self.message_count as u64;
+        self.stream_offset += self.payload.len() as u64;
+        self.clear();
+        Ok(segment)
+    }
+}
+
+/// Encode a decoded packet back to an IEX-TP segment, keeping its header fields.
+///
+/// Known messages are encoded from their fields: bytes a message carries after
+/// them (as a later version of the feed may add) are not kept by the decoder,
+/// and the reserved header byte is written as zero. The segment then differs
+/// from the captured one, with shorter messages and payload.
+pub fn encode_packet(packet: &IEXPacket) -> Result<Vec<u8>, EncodeError> {
+    let header = packet.header.as_ref().ok_or(EncodeError::MissingHeader)?;
+    let mut builder = IexTpSegmentBuilder::from_header(header)?;
+    for message in &packet.payload {
+        builder.push(message)?;
+    }
+    builder.finish(header.send_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packetprocessor::tests::{make_header, timestamp, SYMBOL, TRADING_STATUS_MESSAGE};
+    use crate::packetprocessor::{IEXPacketProcessor, PacketProcessor};
+
+    fn price(s: &str) -> Price {
+        s.parse().unwrap()
+    }
+
+    // One message of every type, with the feed carrying it
+    fn all_messages() -> Vec<(IEXProtocol, IEXMessage)> {
+        let ts = timestamp();
+        vec![
+            (
+                IEXProtocol::Tops,
+                IEXMessage::AuctionInformation(AuctionInformationMessage::from(
+                    AuctionType::CLOSING,
+                    ts,
+                    SYMBOL,
+                    10000,
+                    price("99.05"),
+                    price("99.06"),
+                    500,
+                    ImbalanceSide::Buy,
+                    1,
+                    1471980600,
+                    price("99.07"),
+                    price("99.05"),
+                    price("89.15"),
+                    price("108.96"),
+                )),
+            ),
+            (
+                IEXProtocol::Tops,
+                IEXMessage::SecurityDirectory(SecurityDirectoryMessage::from(
+                    SecurityDirectoryFlags::from_bits(SecurityDirectoryFlags::ETP),
+                    ts,
+                    SYMBOL,
+                    100,
+                    price("99.05"),
+                    LULDTier::Tier1NMS,
+                )),
+            ),
+            (
+                IEXProtocol::Tops,
+                IEXMessage::TradingStatus(TradingStatusMessage::from(
+                    TradingStatus::Halt,
+                    ts,
+                    SYMBOL,
+                    TradingStatusReason::HaltNewsPending,
+                )),
+            ),
+            (
+                IEXProtocol::Tops,
+                IEXMessage::RetailLiquidityIndicator(RetailLiquidityIndicatorMessage::from(
+                    RetailLiquidityIndicator::BuyInterest,
+                    ts,
+                    SYMBOL,
+                )),
+            ),
+            (
+                IEXProtocol::Tops,
+                IEXMessage::ShortSalePriceTestStatus(ShortSalePriceTestStatus::from(
+                    PriceStatus::InEffect,
+                    ts,
+                    SYMBOL,
+                    ShortSaleDetail::ActivatedIntradayPriceDrop,
+                )),
+            ),
+            (
+                IEXProtocol::Tops,
+                IEXMessage::QuoteUpdate(QuoteUpdateMessage::from(
+                    QuoteFlags::default(),
+                    ts,
+                    SYMBOL,
+                    9700,
+                    price("99.05"),
+                    price("99.07"),
+                    1000,
+                )),
+            ),
+            (
+                IEXProtocol::Tops,
+                IEXMessage::TradeReport(TradeReportMessage::from(
+                    SaleConditionFlags::from_bits(SaleConditionFlags::ODD_LOT),
+                    ts,
+                    SYMBOL,
+                    50,
+                    price("99.05"),
+                    429974,
+                )),
+            ),
+            (
+                IEXProtocol::Tops,
+                IEXMessage::SystemEvent(SystemEventMessage::from(
+                    SystemEvent::EndOfSystemHours,
+                    ts,
+                )),
+            ),
+            (
+                IEXProtocol::Tops,
+                IEXMessage::TradeBreak(TradeBreakMessage::from(
+                    SaleConditionFlags::default(),
+                    ts,
+                    SYMBOL,
+                    100,
+                    price("99.05"),
+                    429974,
+                )),
+            ),
+            (
+                IEXProtocol::Tops,
+                IEXMessage::OfficialPrice(OfficialPriceMessage::from(
+                    OfficialPriceType::OpeningPrice,
+                    ts,
+                    SYMBOL,
+                    price("99.05"),
+                )),
+            ),
+            (
+                IEXProtocol::Tops,
+                IEXMessage::OperationalHalt(OperationalHaltMessage::from(
+                    OperationalHaltStatus::Halted,
+                    ts,
+                    SYMBOL,
+                )),
+            ),
+            (
+                IEXProtocol::Deep,
+                IEXMessage::PriceLevelUpdateBuy(PriceLevelUpdateMessage::from(
+                    Side::Buy,
+                    PriceLevelEventFlags::EventProcessingComplete,
+                    ts,
+                    SYMBOL,
+                    9700,
+                    price("99.05"),
+                )),
+            ),
+            (
+                IEXProtocol::Deep,
+                IEXMessage::PriceLevelUpdateSell(PriceLevelUpdateMessage::from(
+                    Side::Sell,
+                    PriceLevelEventFlags::OrderBookProcessingEvent,
+                    ts,
+                    SYMBOL,
+                    1000,
+                    price("99.07"),
+                )),
+            ),
+            (
+                IEXProtocol::Deep,
+                IEXMessage::SecurityEvent(SecurityEventMessage::from(
+                    SecurityEvent::OpeningProcessComplete,
+                    ts,
+                    SYMBOL,
+                )),
+            ),
+            (
+                IEXProtocol::DeepPlus,
+                IEXMessage::AddOrder(AddOrderMessage::from(
+                    Side::Sell,
+                    ts,
+                    SYMBOL,
+                    42,
+                    100,
+                    price("99.07"),
+                )),
+            ),
+            (
+                IEXProtocol::DeepPlus,
+                IEXMessage::OrderModify(OrderModifyMessage::from(
+                    0x01,
+                    ts,
+                    SYMBOL,
+                    42,
+                    50,
+                    price("99.07"),
+                )),
+            ),
+            (
+                IEXProtocol::DeepPlus,
+                IEXMessage::OrderDelete(OrderDeleteMessage::from(ts, SYMBOL, 42)),
+            ),
+            (
+                IEXProtocol::DeepPlus,
+                IEXMessage::OrderExecuted(OrderExecutedMessage::from(
+                    SaleConditionFlags::default(),
+                    ts,
+                    SYMBOL,
+                    42,
+                    50,
+                    price("99.07"),
+                    429975,
+                )),
+            ),
+            (
+                IEXProtocol::Tops,
+                IEXMessage::Unknown {
+                    msg_type: 0x7a,
+                    bytes: vec![0x7a, 0x01, 0x02],
+                },
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_every_message_type_round_trips() {
+        let packet_processor = IEXPacketProcessor {};
+        for (protocol, message) in all_messages() {
+            let mut builder = IexTpSegmentBuilder::new(protocol, 1150681088);
+            builder.push(&message).unwrap();
+            let segment = builder.finish(timestamp()).unwrap();
+            let packet = packet_processor.decode_segment(&segment).unwrap();
+            assert_eq!(packet.payload, vec![message]);
+            assert_eq!(encode_packet(&packet).unwrap(), segment);
+        }
+    }
+
+    #[test]
+    fn test_encoded_segment_matches_the_captured_one() {
+        let segment: Vec<u8> = [
+            bincode::serialize(&make_header(24, 1)).unwrap(),
+            TRADING_STATUS_MESSAGE.to_vec(),
+        ]
+        .concat();
+        let packet = IEXPacketProcessor {}.decode_segment(&segment).unwrap();
+        assert_eq!(encode_packet(&packet).unwrap(), segment);
+
+        let mut builder =
+            IexTpSegmentBuilder::from_header(packet.header.as_ref().unwrap()).unwrap();
+        builder.push(&packet.payload[0]).unwrap();
+        assert_eq!(builder.payload_length(), 24);
+        assert_eq!(
+            builder.finish(packet.header.unwrap().send_time).unwrap(),
+            segment
+        );
+    }
+
+    #[test]
+    fn test_bytes_after_the_known_fields_are_dropped() {
+        // The trading status message with 2 more bytes, as a later feed version could send
+        let mut message = TRADING_STATUS_MESSAGE.to_vec();
+        message[0] = 0x18;
+        message.extend_from_slice(&[0xff, 0xff]);
+        let mut header = make_header(26, 1);
+        header.__reserved = 0x01;
+        let segment = [bincode::serialize(&header).unwrap(), message].concat();
+        let packet = IEXPacketProcessor {}.decode_segment(&segment).unwrap();
+
+        let encoded = encode_packet(&packet).unwrap();
+        let expected_header = IEXHeader {
+            __reserved: 0,
+            payload_length: 24,
+            ..header
+        };
+        let expected: Vec<u8> = [
+            bincode::serialize(&expected_header).unwrap(),
+            TRADING_STATUS_MESSAGE.to_vec(),
+        ]
+        .concat();
+        assert_eq!(encoded, expected);
+        let decoded = IEXPacketProcessor {}.decode_segment(&encoded).unwrap();
+        assert_eq!(decoded.payload, packet.payload);
+    }
+
+    #[test]
+    fn test_sequence_numbers_and_offsets_carry_over() {
+        let packet_processor = IEXPacketProcessor {};
+        let messages: Vec<IEXMessage> = all_messages()
+            .into_iter()
+            .filter(|(protocol, _)| *protocol == IEXProtocol::Tops)
+            .map(|(_, message)| message)
+            .collect();
+        let mut builder =
+            IexTpSegmentBuilder::new(IEXProtocol::Tops, 1).first_message_seq_number(100);
+        for message in &messages[..3] {
+            builder.push(message).unwrap();
+        }
+        let first = packet_processor
+            .decode_segment(&builder.finish(timestamp()).unwrap())
+            .unwrap();
+        let heartbeat = packet_processor
+            .decode_segment(&builder.finish(timestamp()).unwrap())
+            .unwrap();
+        builder.push(&messages[3]).unwrap();
+        let second = packet_processor
+            .decode_segment(&builder.finish(timestamp()).unwrap())
+            .unwrap();
+
+        let first = first.header.unwrap();
+        assert_eq!(first.first_message_seq_number, 100);
+        assert_eq!(first.message_count, 3);
+        assert_eq!(first.stream_offset, 0);
+        let heartbeat = heartbeat.header.unwrap();
+        assert_eq!(heartbeat.message_count, 0);
+        assert_eq!(heartbeat.payload_length, 0);
+        assert_eq!(heartbeat.first_message_seq_number, 103);
+        let second = second.header.unwrap();
+        assert_eq!(second.first_message_seq_number, 103);
+        assert_eq!(second.stream_offset, first.payload_length as u64);
+        assert_eq!(builder.next_seq_number(), 104);
+    }
+
+    #[test]
+    fn test_messages_must_fit_the_segment() {
+        let messages = all_messages();
+        let quote = &messages[5].1;
+        let add_order = &messages[14].1;
+        let mut builder = IexTpSegmentBuilder::new(IEXProtocol::Tops, 1).max_payload_length(50);
+        assert_eq!(
+            builder.push(add_order),
+            Err(EncodeError::UnexpectedMessage {
+                msg_type: 0x61,
+                protocol: IEXProtocol::Tops
+            })
+        );
+        builder.push(quote).unwrap();
+        assert_eq!(builder.push(quote), Err(EncodeError::SegmentFull));
+        assert_eq!(builder.message_count(), 1);
+        assert_eq!(builder.payload_length(), 44);
+
+        let too_long = IEXMessage::Unknown {
+            msg_type: 0x7a,
+            bytes: vec![0x7a; 70000],
+        };
+        assert_eq!(
+            builder.push(&too_long),
+            Err(EncodeError::MessageTooLong(70000))
+        );
+    }
+}
@@ -0,0 +1,267 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use pcap_parser::PcapError;
+
+use crate::iexdata::IEXProtocol;
+
+/// Reason why a packet could not be decoded.
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecodeErrorKind {
+    /// No packet data was handed to the processor
+    MissingPacketData,
+    /// The link layer of the packet is not supported
+    UnsupportedPacketData,
+    /// The buffer ends before the expected structure; `needed` is `None` when
+    /// the size of the structure is not known up front
+    ShortBuffer {
+        needed: Option<usize>,
+        available: usize,
+    },
+    /// The sum of the message lengths does not match the header payload length
+    LengthMismatch { expected: usize, actual: usize },
+    /// A field holds a value outside of its enumeration
+    UnknownDiscriminant(String),
+    /// The IEX-TP version is not supported
+    BadVersion(u8),
+    /// The header protocol id is not a known IEX feed
+    UnsupportedProtocol(u16),
+    /// A pcapng packet refers to an interface not described in its section
+    UnknownInterface(u32),
+}
+
+impl fmt::Display for DecodeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeErrorKind::MissingPacketData => write!(f, "missing packet data"),
+            DecodeErrorKind::UnsupportedPacketData => write!(f, "unsupported packet data"),
+            DecodeErrorKind::ShortBuffer {
+                needed: Some(needed),
+                available,
+            } => write!(
+                f,
+                "short buffer: needed {} bytes, {} available",
+                needed, available
+            ),
+            DecodeErrorKind::ShortBuffer {
+                needed: None,
+                available,
+            } => write!(f, "short buffer: only {} bytes available", available),
+            DecodeErrorKind::LengthMismatch { expected, actual } => write!(
+                f,
+                "length mismatch: expected {} bytes, got {}",
+                expected, actual
+            ),
+            DecodeErrorKind::UnknownDiscriminant(detail) => {
+                write!(f, "unknown enum discriminant: {}", detail)
+            }
+            DecodeErrorKind::BadVersion(version) => write!(f, "bad IEX-TP version {}", version),
+            DecodeErrorKind::UnsupportedProtocol(protocol_id) => {
+                write!(f, "unsupported protocol id 0x{:04x}", protocol_id)
+            }
+            DecodeErrorKind::UnknownInterface(if_id) => write!(f, "unknown interface {}", if_id),
+        }
+    }
+}
+
+/// Error returned when a packet cannot be decoded. Besides the reason it carries
+/// where the failure happened, so that callers can log the packet and skip it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DecodeError {
+    pub kind: DecodeErrorKind,
+    /// Byte offset in the packet data where decoding failed
+    pub offset: usize,
+    /// Sequence number of the first message of the packet, if the header was decoded
+    pub first_message_seq_number: Option<u64>,
+    /// Index of the failing message inside the packet
+    pub message_index: Option<u16>,
+    /// Type byte of the failing message
+    pub msg_type: Option<u8>,
+}
+
+impl DecodeError {
+    pub fn new(kind: DecodeErrorKind, offset: usize) -> DecodeError {
+        DecodeError {
+            kind,
+            offset,
+            first_message_seq_number: None,
+            message_index: None,
+            msg_type: None,
+        }
+    }
+
+    /// Map a bincode error raised while decoding the structure at `offset`
+    pub(crate) fn from_bincode(
+        error: bincode::ErrorKind,
+        offset: usize,
+        available: usize,
+    ) -> DecodeError {
+        let kind = match error {
+            bincode::ErrorKind::Io(_) => DecodeErrorKind::ShortBuffer {
+                needed: None,
+                available,
+            },
+            other => DecodeErrorKind::UnknownDiscriminant(other.to_string()),
+        };
+        DecodeError::new(kind, offset)
+    }
+
+    pub fn with_packet(mut self, first_message_seq_number: u64) -> DecodeError {
+        self.first_message_seq_number = Some(first_message_seq_number);
+        self
+    }
+
+    /// Index of the failing message, when its type byte is not known
+    pub fn with_message_index(mut self, message_index: u16) -> DecodeError {
+        self.message_index = Some(message_index);
+        self
+    }
+
+    pub fn with_message(mut self, message_index: u16, msg_type: u8) -> DecodeError {
+        self.message_index = Some(message_index);
+        self.msg_type = Some(msg_type);
+        self
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot decode packet")?;
+        if let Some(seq) = self.first_message_seq_number {
+            write!(f, " (first seq {})", seq)?;
+        }
+        write!(f, " at offset {}", self.offset)?;
+        if let Some(index) = self.message_index {
+            write!(f, ", message #{}", index)?;
+        }
+        if let Some(msg_type) = self.msg_type {
+            write!(f, " (type 0x{:02x})", msg_type)?;
+        }
+        write!(f, ": {}", self.kind)
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Error returned when messages cannot be encoded into an IEX-TP segment
+#[derive(Debug, PartialEq, Eq)]
+pub enum EncodeError {
+    /// The message type is not carried by the feed of the segment
+    UnexpectedMessage { msg_type: u8, protocol: IEXProtocol },
+    /// The message does not fit its 16 bit length prefix
+    MessageTooLong(usize),
+    /// The message would overflow the segment payload length or message count;
+    /// the segment must be finished before adding more messages
+    SegmentFull,
+    /// The packet has no IEX-TP header to encode
+    MissingHeader,
+    /// The header protocol id is not a known IEX feed
+    UnsupportedProtocol(u16),
+    /// A message or header could not be serialized
+    Serialize(String),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::UnexpectedMessage { msg_type, protocol } => write!(
+                f,
+                "message type 0x{:02x} is not carried by {:?}",
+                msg_type, protocol
+            ),
+            EncodeError::MessageTooLong(length) => {
+                write!(f, "message of {} bytes is too long", length)
+            }
+            EncodeError::SegmentFull => write!(f, "segment is full"),
+            EncodeError::MissingHeader => write!(f, "missing IEX-TP header"),
+            EncodeError::UnsupportedProtocol(protocol_id) => {
+                write!(f, "unsupported protocol id 0x{:04x}", protocol_id)
+            }
+            EncodeError::Serialize(e) => write!(f, "cannot serialize: {}", e),
+        }
+    }
+}
+
+impl Error for EncodeError {}
+
+/// Error yielded while reading packets from a capture file
+#[derive(Debug)]
+pub enum ReaderError {
+    /// The input could not be read or decompressed
+    Io(io::Error),
+    /// The input is not a valid pcap or pcapng file; reading stops
+    Pcap(PcapError<&'static [u8]>),
+    /// A packet could not be decoded; reading goes on with the next block
+    Decode {
+        block_index: u64,
+        error: DecodeError,
+    },
+}
+
+impl fmt::Display for ReaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReaderError::Io(e) => write!(f, "cannot read capture: {}", e),
+            ReaderError::Pcap(e) => write!(f, "cannot parse capture: {}", e),
+            ReaderError::Decode { block_index, error } => {
+                write!(f, "block {}: {}", block_index, error)
+            }
+        }
+    }
+}
+
+impl Error for ReaderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReaderError::Io(e) => Some(e),
+            ReaderError::Pcap(_) => None,
+            ReaderError::Decode { error, .. } => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for ReaderError {
+    fn from(error: io::Error) -> ReaderError {
+        ReaderError::Io(error)
+    }
+}
+
+/// Error returned while writing packets to a capture file
+#[derive(Debug)]
+pub enum WriterError {
+    /// The output could not be written
+    Io(io::Error),
+    /// The packet could not be encoded back to an IEX-TP segment
+    Encode(EncodeError),
+}
+
+impl fmt::Display for WriterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriterError::Io(e) => write!(f, "cannot write capture: {}", e),
+            WriterError::Encode(e) => write!(f, "cannot encode packet: {}", e),
+        }
+    }
+}
+
+impl Error for WriterError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WriterError::Io(e) => Some(e),
+            WriterError::Encode(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for WriterError {
+    fn from(error: io::Error) -> WriterError {
+        WriterError::Io(error)
+    }
+}
+
+impl From<EncodeError> for WriterError {
+    fn from(error: EncodeError) -> WriterError {
+        WriterError::Encode(error)
+    }
+}
@@ -0,0 +1,502 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use chrono::{DateTime, TimeZone, Utc};
+use log::trace;
+
+use pcap_parser::traits::PcapReaderIterator;
+use pcap_parser::{create_reader, Block, Linktype, PcapBlockOwned, PcapError};
+
+use crate::compression::decompressed_reader;
+use crate::error::{DecodeError, DecodeErrorKind, ReaderError};
+use crate::packetprocessor::{CaptureMetadata, IEXPacket, IEXPacketProcessor, PacketProcessor};
+
+const BUFFER_SIZE: usize = 65536;
+
+/// Streams the IEX-TP packets of a pcap or pcapng capture.
+///
+/// The input can be any reader, gzip or zstd compressed or not. Blocks that
+/// are not packets and packets that are not IEX-TP are skipped; a packet that
+/// cannot be decoded is reported as an error and reading goes on with the next
+/// block. Errors in the capture format itself end the iteration.
+pub struct IexFeedReader<'a> {
+    reader: Box<dyn PcapReaderIterator + 'a>,
+    decoder: BlockDecoder,
+    buffer_size: usize,
+    block_index: u64,
+    done: bool,
+}
+
+// pcapng interface, needed to decode the packets captured on it
+struct Interface {
+    linktype: Linktype,
+    // Timestamp units per second, `None` when the if_tsresol option is
+    // invalid, and offset in seconds
+    ts_resolution: Option<u64>,
+    ts_offset: u64,
+}
+
+// Link layer bookkeeping of the capture, kept apart from the reader so that
+// blocks borrowed from the reader buffer can be decoded
+struct BlockDecoder {
+    processor: IEXPacketProcessor,
+    // Interfaces of the current pcapng section
+    interfaces: Vec<Interface>,
+    legacy_linktype: Linktype,
+    legacy_nanosecond: bool,
+}
+
+// Error of a packet captured on an interface the section does not describe
+fn unknown_interface(if_id: u32, block_index: u64) -> ReaderError {
+    ReaderError::Decode {
+        block_index,
+        error: DecodeError::new(DecodeErrorKind::UnknownInterface(if_id), 0),
+    }
+}
+
+// Capture time of `units` timestamp units since `offset` seconds after the epoch
+fn capture_timestamp(units: u64, resolution: u64, offset: u64) -> Option<DateTime<Utc>> {
+    let seconds = offset.checked_add(units / resolution)?;
+    let nanoseconds = (units % resolution) as u128 * 1_000_000_000 / resolution as u128;
+    Utc.timestamp_opt(i64::try_from(seconds).ok()?, nanoseconds as u32)
+        .single()
+}
+
+impl BlockDecoder {
+    // Decode the packet held by `block`, `None` for any other block
+    fn decode(
+        &mut self,
+        block: PcapBlockOwned,
+        block_index: u64,
+    ) -> Option<Result<IEXPacket, ReaderError>> {
+        let (data, metadata) = match block {
+            PcapBlockOwned::NG(Block::SectionHeader(_)) => {
+                // starting a new section, clear known interfaces
+                self.interfaces.clear();
+                return None;
+            }
+            PcapBlockOwned::NG(Block::InterfaceDescription(idb)) => {
+                self.interfaces.push(Interface {
+                    linktype: idb.linktype,
+                    // Microseconds when the if_tsresol option is missing
+                    ts_resolution: idb.ts_resolution(),
+                    ts_offset: idb.ts_offset(),
+                });
+                return None;
+            }
+            PcapBlockOwned::NG(Block::EnhancedPacket(epb)) => {
+                let interface = match self.interfaces.get(epb.if_id as usize) {
+                    Some(interface) => interface,
+                    None => return Some(Err(unknown_interface(epb.if_id, block_index))),
+                };
+                let units = ((epb.ts_high as u64) << 32) | epb.ts_low as u64;
+                let metadata = CaptureMetadata {
+                    block_index,
+                    linktype: interface.linktype,
+                    interface_id: Some(epb.if_id),
+                    timestamp: interface.ts_resolution.and_then(|resolution| {
+                        capture_timestamp(units, resolution, interface.ts_offset)
+                    }),
+                    captured_length: epb.caplen,
+                    original_length: epb.origlen,
+                };
+                (epb.data, metadata)
+            }
+            PcapBlockOwned::NG(Block::SimplePacket(spb)) => {
+                let interface = match self.interfaces.first() {
+                    Some(interface) => interface,
+                    None => return Some(Err(unknown_interface(0, block_index))),
+                };
+                // The data is padded and the packet is cut at the snap length
+                let captured_length = spb.origlen.min(spb.data.len() as u32);
+                let metadata = CaptureMetadata {
+                    block_index,
+                    linktype: interface.linktype,
+                    interface_id: Some(0),
+                    timestamp: None,
+                    captured_length,
+                    original_length: spb.origlen,
+                };
+                (spb.data, metadata)
+            }
+            PcapBlockOwned::LegacyHeader(header) => {
+                self.legacy_linktype = header.network;
+                self.legacy_nanosecond = header.is_nanosecond_precision();
+                return None;
+            }
+            PcapBlockOwned::Legacy(block) => {
+                let resolution = if self.legacy_nanosecond {
+                    1_000_000_000
+                } else {
+                    1_000_000
+                };
+                let metadata = CaptureMetadata {
+                    block_index,
+                    linktype: self.legacy_linktype,
+                    interface_id: None,
+                    timestamp: capture_timestamp(
+                        block.ts_usec as u64,
+                        resolution,
+                        block.ts_sec as u64,
+                    ),
+                    captured_length: block.caplen,
+                    original_length: block.origlen,
+                };
+                (block.data, metadata)
+            }
+            // can be statistics (ISB), name resolution (NRB), etc.
+            PcapBlockOwned::NG(_) => return None,
+        };
+
+        let result = self.processor.process_link_data(
+            data,
+            metadata.linktype,
+            metadata.captured_length as usize,
+        );
+        match result {
+            Ok(Some(mut packet)) => {
+                packet.capture = Some(metadata);
+                Some(Ok(packet))
+            }
+            Ok(None) => {
+                trace!("block {}: not an IEX-TP packet", block_index);
+                None
+            }
+            Err(error) => Some(Err(ReaderError::Decode { block_index, error })),
+        }
+    }
+}
+
+impl<'a> IexFeedReader<'a> {
+    pub fn new<R: Read + 'a>(input: R) -> Result<IexFeedReader<'a>, ReaderError> {
+        let input = decompressed_reader(input)?;
+        // pcapng and legacy pcap (micro or nanosecond timestamps) are detected from the magic number
+        let reader = create_reader(BUFFER_SIZE, input).map_err(ReaderError::Pcap)?;
+        Ok(IexFeedReader {
+            reader,
+            decoder: BlockDecoder {
+                processor: IEXPacketProcessor {},
+                interfaces: vec![],
+                legacy_linktype: Linktype::ETHERNET,
+                legacy_nanosecond: false,
+            },
+            buffer_size: BUFFER_SIZE,
+            block_index: 0,
+            done: false,
+        })
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<IexFeedReader<'static>, ReaderError> {
+        IexFeedReader::new(File::open(path)?)
+    }
+
+    /// Number of pcap blocks read so far
+    pub fn blocks_read(&self) -> u64 {
+        self.block_index
+    }
+
+    // Read more of the input after an incomplete block
+    fn refill(&mut self) -> Result<(), ReaderError> {
+        if self.reader.reader_exhausted() {
+            // The last block is truncated
+            return Err(ReaderError::Pcap(PcapError::Incomplete));
+        }
+        let available = self.reader.data().len();
+        self.reader
+            .refill()
+            .map_err(|e| ReaderError::Pcap(e.to_owned_vec()))?;
+        // Nothing could be read in a full buffer: the block is larger than the buffer
+        if self.reader.data().len() == available && !self.reader.reader_exhausted() {
+            self.buffer_size *= 2;
+            self.reader.grow(self.buffer_size);
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for IexFeedReader<'_> {
+    type Item = Result<IEXPacket, ReaderError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let result = match self.reader.next() {
+                Ok((offset, block)) => {
+                    let result = self.decoder.decode(block, self.block_index);
+                    self.reader.consume(offset);
+                    self.block_index += 1;
+                    result
+                }
+                Err(PcapError::Eof) => {
+                    self.done = true;
+                    None
+                }
+                Err(PcapError::Incomplete) => self.refill().err().map(Err),
+                Err(e) => Some(Err(ReaderError::Pcap(e.to_owned_vec()))),
+            };
+            if let Some(result) = result {
+                // Only decode errors leave the capture readable
+                if let Err(ReaderError::Io(_) | ReaderError::Pcap(_)) = result {
+                    self.done = true;
+                }
+                return Some(result);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+
+    use super::*;
+    use crate::iexdata::IEXMessage;
+    use crate::network::tests::make_frame;
+    use crate::packetprocessor::tests::{make_header, TRADING_STATUS_MESSAGE};
+
+    fn make_segment() -> Vec<u8> {
+        let header = bincode::serialize(&make_header(24, 1)).unwrap();
+        [header, TRADING_STATUS_MESSAGE.to_vec()].concat()
+    }
+
+    // Legacy pcap file with microsecond timestamps holding Ethernet `frames`
+    fn make_pcap(frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut pcap = vec![];
+        pcap.extend_from_slice(&0xa1b2c3d4u32.to_le_bytes());
+        pcap.extend_from_slice(&2u16.to_le_bytes());
+        pcap.extend_from_slice(&4u16.to_le_bytes());
+        pcap.extend_from_slice(&[0; 8]);
+        pcap.extend_from_slice(&65535u32.to_le_bytes());
+        pcap.extend_from_slice(&1u32.to_le_bytes());
+        for (i, frame) in frames.iter().enumerate() {
+            pcap.extend_from_slice(&1503000000u32.to_le_bytes());
+            pcap.extend_from_slice(&(i as u32).to_le_bytes());
+            pcap.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            pcap.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            pcap.extend_from_slice(frame);
+        }
+        pcap
+    }
+
+    #[test]
+    fn test_reads_iex_packets() {
+        let frames = vec![
+            make_frame(&make_segment(), false, 0),
+            // Not an IEX-TP datagram
+            make_frame(&[1, 2, 3], false, 0),
+            make_frame(&make_segment(), true, 0),
+        ];
+        let pcap = make_pcap(&frames);
+        let packets: Vec<IEXPacket> = IexFeedReader::new(pcap.as_slice())
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(packets.len(), 2);
+        assert!(matches!(
+            packets[0].payload[0],
+            IEXMessage::TradingStatus(_)
+        ));
+        assert_eq!(
+            packets[1].capture,
+            Some(CaptureMetadata {
+                block_index: 3,
+                linktype: Linktype::ETHERNET,
+                interface_id: None,
+                timestamp: Some(Utc.timestamp(1503000000, 2000)),
+                captured_length: frames[2].len() as u32,
+                original_length: frames[2].len() as u32,
+            })
+        );
+    }
+
+    // pcapng file with nanosecond timestamps (if_tsresol = 9) holding one
+    // Ethernet `frame` cut at `caplen` bytes
+    fn make_pcapng(frame: &[u8], caplen: usize, timestamp: u64) -> Vec<u8> {
+        let mut pcapng = vec![];
+        // Section header block
+        for word in [0x0a0d0d0a, 28, 0x1a2b3c4d, 1, 0xffffffff, 0xffffffff, 28u32] {
+            pcapng.extend_from_slice(&word.to_le_bytes());
+        }
+        // Interface description block with if_tsresol and opt_endofopt options
+        for word in [1, 32, 1, 65535, 0x00010009, 9, 0, 32u32] {
+            pcapng.extend_from_slice(&word.to_le_bytes());
+        }
+        // Enhanced packet block
+        let padded = caplen.div_ceil(4) * 4;
+        let block_length = (32 + padded) as u32;
+        let header = [
+            6,
+            block_length,
+            0,
+            (timestamp >> 32) as u32,
+            timestamp as u32,
+            caplen as u32,
+            frame.len() as u32,
+        ];
+        for word in header {
+            pcapng.extend_from_slice(&word.to_le_bytes());
+        }
+        pcapng.extend_from_slice(&frame[..caplen]);
+        pcapng.extend(vec![0; padded - caplen]);
+        pcapng.extend_from_slice(&block_length.to_le_bytes());
+        pcapng
+    }
+
+    #[test]
+    fn test_pcapng_capture_metadata() {
+        let mut frame = make_frame(&make_segment(), false, 0);
+        let caplen = frame.len();
+        // Trailing bytes left out of the capture
+        frame.extend_from_slice(&[0; 6]);
+        let pcapng = make_pcapng(&frame, caplen, 1_503_000_000_123_456_789);
+        let packet = IexFeedReader::new(pcapng.as_slice())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let capture = packet.capture.unwrap();
+        assert_eq!(capture.block_index, 2);
+        assert_eq!(capture.interface_id, Some(0));
+        assert_eq!(
+            capture.timestamp,
+            Some(Utc.timestamp(1503000000, 123456789))
+        );
+        assert_eq!(capture.captured_length as usize, caplen);
+        assert_eq!(capture.original_length as usize, caplen + 6);
+        assert!(capture.is_truncated());
+        // The segment send time is 2016-08-23T19:30:32.572715948Z
+        assert_eq!(
+            packet.capture_latency(),
+            Some(Utc.timestamp(1503000000, 123456789) - packet.header.as_ref().unwrap().send_time)
+        );
+    }
+
+    #[test]
+    fn test_capture_format_is_detected() {
+        let frame = make_frame(&make_segment(), false, 0);
+        let mut pcap = make_pcap(std::slice::from_ref(&frame));
+        // Big endian legacy pcap: swap every header field
+        let fields = [(0, 4), (4, 2), (6, 2), (8, 4), (12, 4), (16, 4), (20, 4)];
+        let records = [(24, 4), (28, 4), (32, 4), (36, 4)];
+        for (start, length) in fields.iter().chain(records.iter()) {
+            pcap[*start..start + length].reverse();
+        }
+        let pcapng = make_pcapng(&frame, frame.len(), 1_503_000_000_000_000_000);
+        for capture in [pcap, pcapng] {
+            let packets: Vec<IEXPacket> = IexFeedReader::new(capture.as_slice())
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(packets.len(), 1);
+            assert!(matches!(
+                packets[0].payload[0],
+                IEXMessage::TradingStatus(_)
+            ));
+            assert_eq!(
+                packets[0].capture.unwrap().timestamp,
+                Some(Utc.timestamp(1503000000, 0))
+            );
+        }
+    }
+
+    #[test]
+    fn test_packet_on_unknown_interface_is_an_error() {
+        let frame = make_frame(&make_segment(), false, 0);
+        let mut pcapng = make_pcapng(&frame, frame.len(), 1_503_000_000_000_000_000);
+        // Interface id of the enhanced packet block, after the section and interface blocks
+        pcapng[68..72].copy_from_slice(&1u32.to_le_bytes());
+        let results: Vec<_> = IexFeedReader::new(pcapng.as_slice()).unwrap().collect();
+        assert_eq!(results.len(), 1);
+        match &results[0] {
+            Err(ReaderError::Decode { block_index, error }) => {
+                assert_eq!(*block_index, 2);
+                assert_eq!(error.kind, DecodeErrorKind::UnknownInterface(1));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unrepresentable_pcapng_timestamps_are_left_out() {
+        let frame = make_frame(&make_segment(), false, 0);
+        // Value of the if_tsresol option: out of range, then whole seconds
+        // with a timestamp past the range of chrono
+        for (ts_resol, timestamp) in [(20, 1_503_000_000_000_000_000), (0, u64::MAX)] {
+            let mut pcapng = make_pcapng(&frame, frame.len(), timestamp);
+            pcapng[48] = ts_resol;
+            let packet = IexFeedReader::new(pcapng.as_slice())
+                .unwrap()
+                .next()
+                .unwrap()
+                .unwrap();
+            assert_eq!(packet.capture.unwrap().timestamp, None);
+        }
+    }
+
+    #[test]
+    fn test_legacy_nanosecond_timestamps() {
+        let frame = make_frame(&make_segment(), false, 0);
+        let mut pcap = make_pcap(&[frame.clone(), frame]);
+        pcap[..4].copy_from_slice(&0xa1b23c4du32.to_le_bytes());
+        let packets: Vec<IEXPacket> = IexFeedReader::new(pcap.as_slice())
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            packets[1].capture.unwrap().timestamp,
+            Some(Utc.timestamp(1503000000, 1))
+        );
+    }
+
+    #[test]
+    fn test_decode_errors_do_not_stop_reading() {
+        let mut bad_segment = make_segment();
+        bad_segment.truncate(50);
+        let frames = vec![
+            make_frame(&bad_segment, false, 0),
+            make_frame(&make_segment(), false, 0),
+        ];
+        let pcap = make_pcap(&frames);
+        let results: Vec<_> = IexFeedReader::new(pcap.as_slice()).unwrap().collect();
+        assert_eq!(results.len(), 2);
+        assert!(matches!(
+            results[0],
+            Err(ReaderError::Decode { block_index: 1, .. })
+        ));
+        assert!(results[1].is_ok());
+    }
+
+    #[test]
+    fn test_truncated_capture_ends_with_an_error() {
+        let pcap = make_pcap(&[make_frame(&make_segment(), false, 0)]);
+        let mut reader = IexFeedReader::new(&pcap[..pcap.len() - 5]).unwrap();
+        assert!(matches!(
+            reader.next(),
+            Some(Err(ReaderError::Pcap(PcapError::Incomplete)))
+        ));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_reads_compressed_capture() {
+        let pcap = make_pcap(&[make_frame(&make_segment(), false, 0)]);
+        let mut encoder = GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(&pcap).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut reader = IexFeedReader::new(compressed.as_slice()).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().is_none());
+        assert_eq!(reader.blocks_read(), 2);
+    }
+
+    #[test]
+    fn test_rejects_unknown_format() {
+        assert!(matches!(
+            IexFeedReader::new(&b"not a capture file at all"[..]),
+            Err(ReaderError::Pcap(_))
+        ));
+    }
+}
@@ -0,0 +1,405 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::SocketAddrV4;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+
+use crate::encoder::encode_packet;
+use crate::error::WriterError;
+use crate::network::build_ethernet_udp;
+use crate::packetprocessor::IEXPacket;
+
+const LINKTYPE_ETHERNET: u32 = 1;
+const SNAP_LENGTH: u32 = 65535;
+// Legacy pcap magic number of nanosecond timestamps
+const PCAP_NANOSECOND_MAGIC: u32 = 0xa1b2_3c4d;
+const PCAPNG_SECTION_HEADER: u32 = 0x0a0d_0d0a;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 1;
+const PCAPNG_ENHANCED_PACKET: u32 = 6;
+const PCAPNG_IF_TSRESOL: u16 = 9;
+const PCAPNG_OPT_COMMENT: u16 = 1;
+
+// IEX TOPS multicast group, used unless other endpoints are given
+const DEFAULT_SOURCE: &str = "10.0.0.1:10378";
+const DEFAULT_DESTINATION: &str = "233.215.21.4:10378";
+
+/// File format written by `IexFeedWriter`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CaptureFormat {
+    /// Legacy pcap with nanosecond timestamps
+    Pcap,
+    /// pcapng with a single Ethernet interface and nanosecond timestamps
+    PcapNg,
+}
+
+impl CaptureFormat {
+    /// Format matching the extension of `path`, pcapng unless it ends with `.pcap`
+    pub fn from_path<P: AsRef<Path>>(path: P) -> CaptureFormat {
+        match path.as_ref().extension() {
+            Some(extension) if extension == "pcap" => CaptureFormat::Pcap,
+            _ => CaptureFormat::PcapNg,
+        }
+    }
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// Writes IEX-TP segments to a pcap or pcapng capture.
+///
+/// Each segment is framed in its own Ethernet/IPv4/UDP datagram, so the output
+/// is read back by `IexFeedReader` and by the usual capture tools. Packets read
+/// from a capture keep their endpoints and capture timestamp when re-written.
+pub struct IexFeedWriter<W: Write> {
+    writer: W,
+    format: CaptureFormat,
+    source: SocketAddrV4,
+    destination: SocketAddrV4,
+    packets_written: u64,
+}
+
+impl IexFeedWriter<BufWriter<File>> {
+    /// Create the capture file at `path`, in the format given by its extension
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<IexFeedWriter<BufWriter<File>>> {
+        let format = CaptureFormat::from_path(&path);
+        IexFeedWriter::new(BufWriter::new(File::create(path)?), format)
+    }
+}
+
+impl<W: Write> IexFeedWriter<W> {
+    /// Start a capture on `writer`; the file header is written right away
+    pub fn new(writer: W, format: CaptureFormat) -> io::Result<IexFeedWriter<W>> {
+        IexFeedWriter::start(writer, format, None)
+    }
+
+    /// Start a capture whose pcapng section carries `comment`.
+    /// Legacy pcap files have no room for comments, it is dropped.
+    pub fn new_with_comment(
+        writer: W,
+        format: CaptureFormat,
+        comment: &str,
+    ) -> io::Result<IexFeedWriter<W>> {
+        IexFeedWriter::start(writer, format, Some(comment))
+    }
+
+    fn start(
+        mut writer: W,
+        format: CaptureFormat,
+        comment: Option<&str>,
+    ) -> io::Result<IexFeedWriter<W>> {
+        match format {
+            CaptureFormat::Pcap => {
+                writer.write_all(&PCAP_NANOSECOND_MAGIC.to_le_bytes())?;
+                writer.write_all(&2u16.to_le_bytes())?;
+                writer.write_all(&4u16.to_le_bytes())?;
+                // Time zone and timestamp accuracy
+                writer.write_all(&[0; 8])?;
+                writer.write_all(&SNAP_LENGTH.to_le_bytes())?;
+                writer.write_all(&LINKTYPE_ETHERNET.to_le_bytes())?;
+            }
+            CaptureFormat::PcapNg => {
+                // Section header block of unknown section length, with an optional
+                // shb_comment and opt_endofopt
+                let options = match comment {
+                    Some(comment) => {
+                        let padding = comment.len().div_ceil(4) * 4 - comment.len();
+                        let length = u16::try_from(comment.len())
+                            .map_err(|_| invalid_input("comment too long"))?;
+                        let mut options = vec![];
+                        options.extend_from_slice(&PCAPNG_OPT_COMMENT.to_le_bytes());
+                        options.extend_from_slice(&length.to_le_bytes());
+                        options.extend_from_slice(comment.as_bytes());
+                        options.extend(vec![0; padding]);
+                        options.extend_from_slice(&[0; 4]);
+                        options
+                    }
+                    None => vec![],
+                };
+                let block_length = 28 + options.len() as u32;
+                for word in [
+                    PCAPNG_SECTION_HEADER,
+                    block_length,
+                    PCAPNG_BYTE_ORDER_MAGIC,
+                    1,
+                ] {
+                    writer.write_all(&word.to_le_bytes())?;
+                }
+                writer.write_all(&u64::MAX.to_le_bytes())?;
+                writer.write_all(&options)?;
+                writer.write_all(&block_length.to_le_bytes())?;
+                // Interface description block with nanosecond if_tsresol
+                writer.write_all(&PCAPNG_INTERFACE_DESCRIPTION.to_le_bytes())?;
+                writer.write_all(&32u32.to_le_bytes())?;
+                writer.write_all(&(LINKTYPE_ETHERNET as u16).to_le_bytes())?;
+                writer.write_all(&[0; 2])?;
+                writer.write_all(&SNAP_LENGTH.to_le_bytes())?;
+                writer.write_all(&PCAPNG_IF_TSRESOL.to_le_bytes())?;
+                writer.write_all(&1u16.to_le_bytes())?;
+                writer.write_all(&[9, 0, 0, 0])?;
+                // opt_endofopt
+                writer.write_all(&[0; 4])?;
+                writer.write_all(&32u32.to_le_bytes())?;
+            }
+        }
+        Ok(IexFeedWriter {
+            writer,
+            format,
+            source: DEFAULT_SOURCE.parse().unwrap(),
+            destination: DEFAULT_DESTINATION.parse().unwrap(),
+            packets_written: 0,
+        })
+    }
+
+    /// UDP endpoints of the segments written with `write_segment`
+    pub fn with_endpoints(
+        mut self,
+        source: SocketAddrV4,
+        destination: SocketAddrV4,
+    ) -> IexFeedWriter<W> {
+        self.source = source;
+        self.destination = destination;
+        self
+    }
+
+    pub fn format(&self) -> CaptureFormat {
+        self.format
+    }
+
+    pub fn packets_written(&self) -> u64 {
+        self.packets_written
+    }
+
+    /// Write a raw IEX-TP segment captured at `timestamp`
+    pub fn write_segment(&mut self, segment: &[u8], timestamp: DateTime<Utc>) -> io::Result<()> {
+        let (source, destination) = (self.source, self.destination);
+        self.write_datagram(source, destination, segment, timestamp)
+    }
+
+    /// Encode `packet` back to its segment and write it. The capture timestamp
+    /// and UDP endpoints of the packet are kept when known, the send time and
+    /// the writer endpoints are used otherwise.
+    pub fn write_packet(&mut self, packet: &IEXPacket) -> Result<(), WriterError> {
+        let segment = encode_packet(packet)?;
+        let timestamp = packet
+            .capture
+            .and_then(|capture| capture.timestamp)
+            .or_else(|| packet.header.as_ref().map(|header| header.send_time))
+            .unwrap_or_else(Utc::now);
+        let source = match packet.source {
+            Some(source) if !source.ip().is_unspecified() => source,
+            _ => self.source,
+        };
+        let destination = match packet.destination {
+            Some(destination) if !destination.ip().is_unspecified() => destination,
+            _ => self.destination,
+        };
+        self.write_datagram(source, destination, &segment, timestamp)?;
+        Ok(())
+    }
+
+    fn write_datagram(
+        &mut self,
+        source: SocketAddrV4,
+        destination: SocketAddrV4,
+        segment: &[u8],
+        timestamp: DateTime<Utc>,
+    ) -> io::Result<()> {
+        // The IPv4 identification only has to differ between recent packets
+        let identification = self.packets_written as u16;
+        let frame = build_ethernet_udp(source, destination, identification, segment)
+            .ok_or_else(|| invalid_input("segment too long for a UDP datagram"))?;
+        if timestamp.timestamp() < 0 {
+            return Err(invalid_input("capture timestamp before 1970"));
+        }
+        let length = frame.len() as u32;
+        match self.format {
+            CaptureFormat::Pcap => {
+                let seconds = u32::try_from(timestamp.timestamp())
+                    .map_err(|_| invalid_input("capture timestamp after 2106"))?;
+                self.writer.write_all(&seconds.to_le_bytes())?;
+                self.writer
+                    .write_all(&timestamp.timestamp_subsec_nanos().to_le_bytes())?;
+                self.writer.write_all(&length.to_le_bytes())?;
+                self.writer.write_all(&length.to_le_bytes())?;
+                self.writer.write_all(&frame)?;
+            }
+            CaptureFormat::PcapNg => {
+                // Nanoseconds since 1970 in 64 bits, up to year 2554
+                let units = (timestamp.timestamp() as u64)
+                    .checked_mul(1_000_000_000)
+                    .and_then(|nanos| nanos.checked_add(timestamp.timestamp_subsec_nanos() as u64))
+                    .ok_or_else(|| invalid_input("capture timestamp after 2554"))?;
+                let padding = frame.len().div_ceil(4) * 4 - frame.len();
+                let block_length = 32 + length + padding as u32;
+                let header = [
+                    PCAPNG_ENHANCED_PACKET,
+                    block_length,
+                    // Interface id
+                    0,
+                    (units >> 32) as u32,
+                    units as u32,
+                    length,
+                    length,
+                ];
+                for word in header {
+                    self.writer.write_all(&word.to_le_bytes())?;
+                }
+                self.writer.write_all(&frame)?;
+                self.writer.write_all(&[0; 3][..padding])?;
+                self.writer.write_all(&block_length.to_le_bytes())?;
+            }
+        }
+        self.packets_written += 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Flush and return the underlying writer
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::encoder::IexTpSegmentBuilder;
+    use crate::feedreader::IexFeedReader;
+    use crate::iexdata::*;
+    use crate::packetprocessor::tests::{timestamp, SYMBOL};
+
+    fn make_segments(count: usize) -> Vec<Vec<u8>> {
+        let mut builder = IexTpSegmentBuilder::new(IEXProtocol::Tops, 1150681088);
+        (0..count)
+            .map(|i| {
+                builder
+                    .push(&IEXMessage::TradingStatus(TradingStatusMessage::from(
+                        TradingStatus::Halt,
+                        timestamp(),
+                        SYMBOL,
+                        TradingStatusReason::HaltNewsPending,
+                    )))
+                    .unwrap();
+                builder
+                    .finish(timestamp() + chrono::Duration::seconds(i as i64))
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    fn read_back(capture: &[u8]) -> Vec<IEXPacket> {
+        IexFeedReader::new(capture)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_written_segments_read_back() {
+        for format in [CaptureFormat::Pcap, CaptureFormat::PcapNg] {
+            let segments = make_segments(3);
+            let mut writer = IexFeedWriter::new(vec![], format).unwrap();
+            for (i, segment) in segments.iter().enumerate() {
+                let captured = timestamp() + chrono::Duration::nanoseconds(i as i64 + 1);
+                writer.write_segment(segment, captured).unwrap();
+            }
+            assert_eq!(writer.packets_written(), 3);
+            let packets = read_back(&writer.into_inner().unwrap());
+            assert_eq!(packets.len(), 3);
+            for (i, packet) in packets.iter().enumerate() {
+                let header = packet.header.as_ref().unwrap();
+                assert_eq!(header.first_message_seq_number, i as u64 + 1);
+                assert_eq!(
+                    packet.capture.unwrap().timestamp,
+                    Some(timestamp() + chrono::Duration::nanoseconds(i as i64 + 1))
+                );
+                assert_eq!(packet.source, Some(DEFAULT_SOURCE.parse().unwrap()));
+                assert_eq!(
+                    packet.destination,
+                    Some(DEFAULT_DESTINATION.parse().unwrap())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_rewritten_packets_keep_capture_details() {
+        let source = "10.1.2.3:5000".parse().unwrap();
+        let destination = "233.215.21.8:10379".parse().unwrap();
+        let mut writer = IexFeedWriter::new(vec![], CaptureFormat::Pcap)
+            .unwrap()
+            .with_endpoints(source, destination);
+        for segment in make_segments(2) {
+            writer.write_segment(&segment, timestamp()).unwrap();
+        }
+        let original = writer.into_inner().unwrap();
+        let packets = read_back(&original);
+
+        let mut writer = IexFeedWriter::new(vec![], CaptureFormat::PcapNg).unwrap();
+        for packet in &packets {
+            writer.write_packet(packet).unwrap();
+        }
+        let rewritten = read_back(&writer.into_inner().unwrap());
+        assert_eq!(rewritten.len(), 2);
+        for (packet, original) in rewritten.iter().zip(&packets) {
+            assert_eq!(packet.header, original.header);
+            assert_eq!(packet.payload, original.payload);
+            assert_eq!(packet.source, Some(source));
+            assert_eq!(packet.destination, Some(destination));
+            assert_eq!(
+                packet.capture.unwrap().timestamp,
+                original.capture.unwrap().timestamp
+            );
+        }
+    }
+
+    #[test]
+    fn test_timestamps_out_of_range_are_refused() {
+        let segment = &make_segments(1)[0];
+        let late = DateTime::<Utc>::from_str("2300-01-01T00:00:00.000000001Z").unwrap();
+        let mut writer = IexFeedWriter::new(vec![], CaptureFormat::PcapNg).unwrap();
+        writer.write_segment(segment, late).unwrap();
+        let too_late = DateTime::<Utc>::from_str("2600-01-01T00:00:00Z").unwrap();
+        let error = writer.write_segment(segment, too_late).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        let early = DateTime::<Utc>::from_str("1969-12-31T23:59:59Z").unwrap();
+        assert!(writer.write_segment(segment, early).is_err());
+        assert_eq!(writer.packets_written(), 1);
+
+        let mut writer = IexFeedWriter::new(vec![], CaptureFormat::Pcap).unwrap();
+        assert!(writer.write_segment(segment, late).is_err());
+    }
+
+    #[test]
+    fn test_section_comment() {
+        let mut writer =
+            IexFeedWriter::new_with_comment(vec![], CaptureFormat::PcapNg, "re-sequenced").unwrap();
+        writer
+            .write_segment(&make_segments(1)[0], timestamp())
+            .unwrap();
+        let capture = writer.into_inner().unwrap();
+        // Section header block of 28 bytes plus the padded comment and the end of options
+        assert_eq!(&capture[4..8], &48u32.to_le_bytes());
+        assert_eq!(&capture[24..40], b"\x01\x00\x0c\x00re-sequenced");
+        assert_eq!(read_back(&capture).len(), 1);
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(CaptureFormat::from_path("out.pcap"), CaptureFormat::Pcap);
+        assert_eq!(
+            CaptureFormat::from_path("out.pcapng"),
+            CaptureFormat::PcapNg
+        );
+        assert_eq!(CaptureFormat::from_path("out"), CaptureFormat::PcapNg);
+    }
+}
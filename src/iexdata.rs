@@ -7,11 +7,11 @@ use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use std::fmt;
 use std::str;
-use time::serde::timestamp;
 
-const K_MULT: f64 = 1e-4;
+pub use crate::price::Price;
+pub use crate::symbol::Symbol;
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
 pub struct IEXHeader {
     pub version: u8,
     pub(crate) __reserved: u8,
@@ -26,6 +26,34 @@ pub struct IEXHeader {
     pub send_time: DateTime<Utc>,
 }
 
+impl IEXHeader {
+    pub fn protocol(&self) -> Option<IEXProtocol> {
+        IEXProtocol::from_id(self.protocol_id)
+    }
+}
+
+/// Feed carried by an IEX-TP segment, identified by the header `protocol_id`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[repr(u16)]
+pub enum IEXProtocol {
+    Tops = 0x8003,
+    Deep = 0x8004,
+    // Message Protocol ID of the IEX-TP header in the DEEP+ specification
+    // (IEX DEEP+ Specification, "IEX-TP Header" section)
+    DeepPlus = 0x8006,
+}
+
+impl IEXProtocol {
+    pub fn from_id(protocol_id: u16) -> Option<IEXProtocol> {
+        match protocol_id {
+            0x8003 => Some(IEXProtocol::Tops),
+            0x8004 => Some(IEXProtocol::Deep),
+            0x8006 => Some(IEXProtocol::DeepPlus),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct IEXMessageData {
     pub length: u16,
@@ -47,7 +75,92 @@ pub enum IEXMessageType {
     SystemEventMessage = 0x53,
     TradeReportMessage = 0x54,
     OfficialPriceMessage = 0x58,
-    Nothing,
+    // DEEP
+    PriceLevelUpdateBuy = 0x38,
+    PriceLevelUpdateSell = 0x35,
+    SecurityEventMessage = 0x45,
+    // DEEP+
+    OrderModifyMessage = 0x4d,
+    OrderDeleteMessage = 0x52,
+    OrderExecutedMessage = 0x4c,
+    AddOrderMessage = 0x61,
+    Nothing = 0x59,
+}
+
+/// A decoded IEX message, one variant per supported `IEXMessageType`.
+/// Messages whose type is not decoded yet are kept as raw bytes in `Unknown`.
+#[derive(Debug, PartialEq)]
+pub enum IEXMessage {
+    AuctionInformation(AuctionInformationMessage),
+    SecurityDirectory(SecurityDirectoryMessage),
+    TradingStatus(TradingStatusMessage),
+    RetailLiquidityIndicator(RetailLiquidityIndicatorMessage),
+    ShortSalePriceTestStatus(ShortSalePriceTestStatus),
+    QuoteUpdate(QuoteUpdateMessage),
+    TradeReport(TradeReportMessage),
+    SystemEvent(SystemEventMessage),
+    TradeBreak(TradeBreakMessage),
+    OfficialPrice(OfficialPriceMessage),
+    OperationalHalt(OperationalHaltMessage),
+    PriceLevelUpdateBuy(PriceLevelUpdateMessage),
+    PriceLevelUpdateSell(PriceLevelUpdateMessage),
+    SecurityEvent(SecurityEventMessage),
+    AddOrder(AddOrderMessage),
+    OrderModify(OrderModifyMessage),
+    OrderDelete(OrderDeleteMessage),
+    OrderExecuted(OrderExecutedMessage),
+    Unknown { msg_type: u8, bytes: Vec<u8> },
+}
+
+impl IEXMessage {
+    /// Type byte of the message, as sent on the wire
+    pub fn msg_type(&self) -> u8 {
+        match self {
+            IEXMessage::AuctionInformation(message) => message.__t,
+            IEXMessage::SecurityDirectory(message) => message.__t,
+            IEXMessage::TradingStatus(message) => message.__t,
+            IEXMessage::RetailLiquidityIndicator(message) => message.__t,
+            IEXMessage::ShortSalePriceTestStatus(message) => message.__t,
+            IEXMessage::QuoteUpdate(message) => message.t,
+            IEXMessage::TradeReport(message) => message.__type,
+            IEXMessage::SystemEvent(message) => message.__t,
+            IEXMessage::TradeBreak(message) => message.__type,
+            IEXMessage::OfficialPrice(message) => message.__t,
+            IEXMessage::OperationalHalt(message) => message.__t,
+            IEXMessage::PriceLevelUpdateBuy(message) => message.__t,
+            IEXMessage::PriceLevelUpdateSell(message) => message.__t,
+            IEXMessage::SecurityEvent(message) => message.__t,
+            IEXMessage::AddOrder(message) => message.__t,
+            IEXMessage::OrderModify(message) => message.__t,
+            IEXMessage::OrderDelete(message) => message.__t,
+            IEXMessage::OrderExecuted(message) => message.__t,
+            IEXMessage::Unknown { msg_type, .. } => *msg_type,
+        }
+    }
+
+    /// Symbol the message is about, `None` for system wide and unknown messages
+    pub fn symbol(&self) -> Option<Symbol> {
+        match self {
+            IEXMessage::AuctionInformation(message) => Some(message.symbol),
+            IEXMessage::SecurityDirectory(message) => Some(message.symbol),
+            IEXMessage::TradingStatus(message) => Some(message.symbol),
+            IEXMessage::RetailLiquidityIndicator(message) => Some(message.symbol),
+            IEXMessage::ShortSalePriceTestStatus(message) => Some(message.symbol),
+            IEXMessage::QuoteUpdate(message) => Some(message.symbol),
+            IEXMessage::TradeReport(message) => Some(message.symbol),
+            IEXMessage::TradeBreak(message) => Some(message.symbol),
+            IEXMessage::OfficialPrice(message) => Some(message.symbol),
+            IEXMessage::OperationalHalt(message) => Some(message.symbol),
+            IEXMessage::PriceLevelUpdateBuy(message) => Some(message.symbol),
+            IEXMessage::PriceLevelUpdateSell(message) => Some(message.symbol),
+            IEXMessage::SecurityEvent(message) => Some(message.symbol),
+            IEXMessage::AddOrder(message) => Some(message.symbol),
+            IEXMessage::OrderModify(message) => Some(message.symbol),
+            IEXMessage::OrderDelete(message) => Some(message.symbol),
+            IEXMessage::OrderExecuted(message) => Some(message.symbol),
+            IEXMessage::SystemEvent(_) | IEXMessage::Unknown { .. } => None,
+        }
+    }
 }
 
 // AUCTION MESSAGES
@@ -70,29 +183,66 @@ pub enum ImbalanceSide {
     Unknown,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, PartialEq)]
 pub struct AuctionInformationMessage {
     __t: u8,
     pub auction_type: AuctionType,
     #[serde(with = "ts_nanoseconds")]
     pub send_time: DateTime<Utc>,
-    pub symbol: [u8; 8],
+    pub symbol: Symbol,
     pub paired_shares: u32,
-    pub reference_price: i64,
-    pub indicative_price: i64,
+    pub reference_price: Price,
+    pub indicative_price: Price,
     pub imbalance_shares: u32,
     pub imbalance_side: ImbalanceSide,
     pub extension_number: u8,
     pub scheduled_auction_time: u32,
-    pub auction_book_clearing_price: i64,
-    pub collar_reference_price: i64,
-    pub lower_auction_collar: i64,
-    pub upper_auction_collar: i64,
+    pub auction_book_clearing_price: Price,
+    pub collar_reference_price: Price,
+    pub lower_auction_collar: Price,
+    pub upper_auction_collar: Price,
+}
+
+impl AuctionInformationMessage {
+    #[allow(clippy::too_many_arguments)]
+    pub fn from(
+        auction_type: AuctionType,
+        send_time: DateTime<Utc>,
+        symbol: Symbol,
+        paired_shares: u32,
+        reference_price: Price,
+        indicative_price: Price,
+        imbalance_shares: u32,
+        imbalance_side: ImbalanceSide,
+        extension_number: u8,
+        scheduled_auction_time: u32,
+        auction_book_clearing_price: Price,
+        collar_reference_price: Price,
+        lower_auction_collar: Price,
+        upper_auction_collar: Price,
+    ) -> AuctionInformationMessage {
+        AuctionInformationMessage {
+            __t: IEXMessageType::AuctionInformationMessage as u8,
+            auction_type,
+            send_time,
+            symbol,
+            paired_shares,
+            reference_price,
+            indicative_price,
+            imbalance_shares,
+            imbalance_side,
+            extension_number,
+            scheduled_auction_time,
+            auction_book_clearing_price,
+            collar_reference_price,
+            lower_auction_collar,
+            upper_auction_collar,
+        }
+    }
 }
 
 impl fmt::Debug for AuctionInformationMessage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let symbol = String::from_utf8(self.symbol.to_vec()).unwrap_or("NONE".to_string());
         // Create a NaiveDateTime from the timestamp
         let naive = NaiveDateTime::from_timestamp(self.scheduled_auction_time as i64, 0);
         // Create a normal DateTime from the NaiveDateTime
@@ -100,61 +250,279 @@ impl fmt::Debug for AuctionInformationMessage {
 
         f.debug_struct("AuctionInformationMessage")
             .field("send time", &self.send_time)
-            .field("symbol", &symbol.trim())
+            .field("symbol", &self.symbol)
             .field("paired shares", &self.paired_shares)
-            .field("reference price", &((self.reference_price as f64) * K_MULT))
-            .field(
-                "indicative price",
-                &((self.indicative_price as f64) * K_MULT),
-            )
+            .field("reference price", &self.reference_price)
+            .field("indicative price", &self.indicative_price)
             .field("imbalance shares", &self.imbalance_shares)
             .field("imbalance side", &self.imbalance_side)
             .field("extension_number", &self.extension_number)
             .field("scheduled auction time", &naive_datetime)
-            .field(
-                "auction book clearing price",
-                &((self.auction_book_clearing_price as f64) * K_MULT),
-            )
-            .field(
-                "collar reference price",
-                &((self.collar_reference_price as f64) * K_MULT),
-            )
-            .field(
-                "lower auction collar",
-                &((self.lower_auction_collar as f64) * K_MULT),
-            )
-            .field(
-                "upper auction collar",
-                &((self.upper_auction_collar as f64) * K_MULT),
-            )
+            .field("auction book clearing price", &self.auction_book_clearing_price)
+            .field("collar reference price", &self.collar_reference_price)
+            .field("lower auction collar", &self.lower_auction_collar)
+            .field("upper auction collar", &self.upper_auction_collar)
             .finish()
     }
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Deserialize)]
+// Write the names of the flags set in `bits`, e.g. `SaleConditionFlags(OddLot | ExtendedHours)`
+fn fmt_flags(f: &mut fmt::Formatter<'_>, name: &str, bits: u8, names: &[(u8, &str)]) -> fmt::Result {
+    write!(f, "{}(", name)?;
+    let mut first = true;
+    for &(bit, bit_name) in names {
+        if bits & bit != 0 {
+            write!(f, "{}{}", if first { "" } else { " | " }, bit_name)?;
+            first = false;
+        }
+    }
+    if first {
+        write!(f, "empty")?;
+    }
+    write!(f, ")")
+}
+
+// Quote update flags
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(transparent)]
+pub struct QuoteFlags(u8);
+
+impl QuoteFlags {
+    // Symbol is halted, paused or otherwise not available for trading on IEX
+    pub const SYMBOL_UNAVAILABLE: u8 = 0x80;
+    // The quote is sent outside of the regular market session
+    pub const PRE_POST_MARKET_SESSION: u8 = 0x40;
+
+    pub const fn from_bits(bits: u8) -> QuoteFlags {
+        QuoteFlags(bits)
+    }
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub fn is_symbol_available(self) -> bool {
+        self.0 & QuoteFlags::SYMBOL_UNAVAILABLE == 0
+    }
+
+    pub fn is_regular_market_session(self) -> bool {
+        self.0 & QuoteFlags::PRE_POST_MARKET_SESSION == 0
+    }
+}
+
+impl fmt::Debug for QuoteFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuoteFlags")
+            .field("symbol available", &self.is_symbol_available())
+            .field("regular market session", &self.is_regular_market_session())
+            .finish()
+    }
+}
+
+// Sale condition flags of trade reports, trade breaks and order executions
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(transparent)]
+pub struct SaleConditionFlags(u8);
+
+impl SaleConditionFlags {
+    pub const INTERMARKET_SWEEP: u8 = 0x80;
+    pub const EXTENDED_HOURS: u8 = 0x40;
+    pub const ODD_LOT: u8 = 0x20;
+    pub const TRADE_THROUGH_EXEMPT: u8 = 0x10;
+    pub const SINGLE_PRICE_CROSS: u8 = 0x08;
+
+    pub const fn from_bits(bits: u8) -> SaleConditionFlags {
+        SaleConditionFlags(bits)
+    }
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    // Intermarket sweep order (ISO)
+    pub fn is_intermarket_sweep(self) -> bool {
+        self.0 & SaleConditionFlags::INTERMARKET_SWEEP != 0
+    }
+
+    // Executed outside of the regular market session
+    pub fn is_extended_hours(self) -> bool {
+        self.0 & SaleConditionFlags::EXTENDED_HOURS != 0
+    }
+
+    pub fn is_odd_lot(self) -> bool {
+        self.0 & SaleConditionFlags::ODD_LOT != 0
+    }
+
+    // Exempt from the Order Protection Rule of Reg NMS
+    pub fn is_trade_through_exempt(self) -> bool {
+        self.0 & SaleConditionFlags::TRADE_THROUGH_EXEMPT != 0
+    }
+
+    // Trade resulting from a single-price cross (auction)
+    pub fn is_single_price_cross(self) -> bool {
+        self.0 & SaleConditionFlags::SINGLE_PRICE_CROSS != 0
+    }
+}
+
+impl fmt::Debug for SaleConditionFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_flags(
+            f,
+            "SaleConditionFlags",
+            self.0,
+            &[
+                (SaleConditionFlags::INTERMARKET_SWEEP, "IntermarketSweep"),
+                (SaleConditionFlags::EXTENDED_HOURS, "ExtendedHours"),
+                (SaleConditionFlags::ODD_LOT, "OddLot"),
+                (SaleConditionFlags::TRADE_THROUGH_EXEMPT, "TradeThroughExempt"),
+                (SaleConditionFlags::SINGLE_PRICE_CROSS, "SinglePriceCross"),
+            ],
+        )
+    }
+}
+
+// Security directory flags
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(transparent)]
+pub struct SecurityDirectoryFlags(u8);
+
+impl SecurityDirectoryFlags {
+    pub const TEST_SECURITY: u8 = 0x80;
+    pub const WHEN_ISSUED: u8 = 0x40;
+    pub const ETP: u8 = 0x20;
+
+    pub const fn from_bits(bits: u8) -> SecurityDirectoryFlags {
+        SecurityDirectoryFlags(bits)
+    }
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub fn is_test_security(self) -> bool {
+        self.0 & SecurityDirectoryFlags::TEST_SECURITY != 0
+    }
+
+    pub fn is_when_issued(self) -> bool {
+        self.0 & SecurityDirectoryFlags::WHEN_ISSUED != 0
+    }
+
+    // Exchange traded product
+    pub fn is_etp(self) -> bool {
+        self.0 & SecurityDirectoryFlags::ETP != 0
+    }
+}
+
+impl fmt::Debug for SecurityDirectoryFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_flags(
+            f,
+            "SecurityDirectoryFlags",
+            self.0,
+            &[
+                (SecurityDirectoryFlags::TEST_SECURITY, "TestSecurity"),
+                (SecurityDirectoryFlags::WHEN_ISSUED, "WhenIssued"),
+                (SecurityDirectoryFlags::ETP, "Etp"),
+            ],
+        )
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Deserialize, Serialize, PartialEq)]
 pub struct TradeReportMessage {
     __type: u8,
-    pub sale_condition_flags: u8,
+    pub sale_condition_flags: SaleConditionFlags,
     #[serde(with = "ts_nanoseconds")]
     pub timestamp: DateTime<Utc>,
-    pub symbol: [u8; 8],
+    pub symbol: Symbol,
     pub size: u32,
-    pub price: i64,
+    pub price: Price,
     pub trade_id: u64,
 }
 
+impl TradeReportMessage {
+    pub fn from(
+        sale_condition_flags: SaleConditionFlags,
+        timestamp: DateTime<Utc>,
+        symbol: Symbol,
+        size: u32,
+        price: Price,
+        trade_id: u64,
+    ) -> TradeReportMessage {
+        TradeReportMessage {
+            __type: IEXMessageType::TradeReportMessage as u8,
+            sale_condition_flags,
+            timestamp,
+            symbol,
+            size,
+            price,
+            trade_id,
+        }
+    }
+}
+
 impl fmt::Debug for TradeReportMessage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let symbol = String::from_utf8(self.symbol.to_vec()).unwrap_or("NONE".to_string());
-
         f.debug_struct("TradeReportMessage")
             .field("sale condition flags", &self.sale_condition_flags)
             .field("timestamp", &self.timestamp)
-            .field("symbol", &symbol.trim())
+            .field("symbol", &self.symbol)
             .field("size", &self.size)
-            .field("price", &((self.price as f64) * K_MULT))
+            .field("price", &self.price)
+            .field("trade id", &self.trade_id)
+            .finish()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+// Trade break: sent when an execution on IEX is broken on that same trading day.
+// The fields are the same as the broken trade report.
+#[derive(Deserialize, Serialize, PartialEq)]
+pub struct TradeBreakMessage {
+    __type: u8,
+    pub sale_condition_flags: SaleConditionFlags,
+    #[serde(with = "ts_nanoseconds")]
+    pub timestamp: DateTime<Utc>,
+    pub symbol: Symbol,
+    pub size: u32,
+    pub price: Price,
+    pub trade_id: u64,
+}
+
+impl TradeBreakMessage {
+    pub fn from(
+        sale_condition_flags: SaleConditionFlags,
+        timestamp: DateTime<Utc>,
+        symbol: Symbol,
+        size: u32,
+        price: Price,
+        trade_id: u64,
+    ) -> TradeBreakMessage {
+        TradeBreakMessage {
+            __type: IEXMessageType::TradeBreakMessage as u8,
+            sale_condition_flags,
+            timestamp,
+            symbol,
+            size,
+            price,
+            trade_id,
+        }
+    }
+}
+
+impl fmt::Debug for TradeBreakMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TradeBreakMessage")
+            .field("sale condition flags", &self.sale_condition_flags)
+            .field("timestamp", &self.timestamp)
+            .field("symbol", &self.symbol)
+            .field("size", &self.size)
+            .field("price", &self.price)
             .field("trade id", &self.trade_id)
             .finish()
     }
@@ -166,57 +534,55 @@ impl fmt::Debug for TradeReportMessage {
 #[derive(Deserialize, Serialize, PartialEq)]
 pub struct QuoteUpdateMessage {
     t: u8,
-    pub flags: u8,
+    pub flags: QuoteFlags,
     #[serde(with = "ts_nanoseconds")]
     pub timestamp: DateTime<Utc>,
-    pub symbol: [u8; 8],
+    pub symbol: Symbol,
     pub bid_size: u32,
-    pub bid_price: i64,
-    pub ask_price: i64,
+    pub bid_price: Price,
+    pub ask_price: Price,
     pub ask_size: u32,
 }
 
 impl QuoteUpdateMessage {
     pub fn from(
-        flags : u8,
+        flags: QuoteFlags,
         timestamp: DateTime<Utc>,
-        symbol: [u8; 8],
+        symbol: Symbol,
         bid_size: u32,
-        bid_price: f32,
-        ask_price: f32,
+        bid_price: Price,
+        ask_price: Price,
         ask_size: u32,
     ) -> QuoteUpdateMessage {
         QuoteUpdateMessage {
             t: IEXMessageType::QuoteUpdateMessage as u8,
-            flags: flags,
-            timestamp: timestamp,
-            symbol: symbol,
-            bid_size: bid_size,
-            bid_price: (bid_price * 10000 as f32) as i64,
-            ask_price: (ask_price * 10000 as f32) as i64,
-            ask_size: ask_size,
+            flags,
+            timestamp,
+            symbol,
+            bid_size,
+            bid_price,
+            ask_price,
+            ask_size,
         }
     }
 }
 
 impl fmt::Debug for QuoteUpdateMessage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let symbol = String::from_utf8(self.symbol.to_vec()).unwrap_or("NONE".to_string());
-
         f.debug_struct("QuoteUpdateMessage")
             .field("flags", &self.flags)
             .field("timestamp", &self.timestamp)
-            .field("symbol", &symbol.trim())
+            .field("symbol", &self.symbol)
             .field("bid_size", &self.bid_size)
-            .field("bid price", &((self.bid_price as f64) * 1e-4))
-            .field("ask price", &((self.ask_price as f64) * 1e-4))
+            .field("bid price", &self.bid_price)
+            .field("ask price", &self.ask_price)
             .field("ask_size", &self.ask_size)
             .finish()
     }
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////////////////
-#[derive(Deserialize_repr, Serialize_repr, Debug, PartialEq)]
+#[derive(Deserialize_repr, Serialize_repr, Debug, PartialEq, Clone, Copy)]
 #[repr(u8)]
 pub enum PriceStatus {
     NotInEffect = 0x0,
@@ -230,24 +596,89 @@ pub struct ShortSalePriceTestStatus {
     pub price_status: PriceStatus,
     #[serde(with = "ts_nanoseconds")]
     pub timestamp: DateTime<Utc>,
-    pub symbol: [u8; 8],
-    pub detail: u8,
+    pub symbol: Symbol,
+    pub detail: ShortSaleDetail,
 }
 
 impl ShortSalePriceTestStatus
 {
-    pub fn from(price_status : PriceStatus, timestamp : DateTime<Utc>, symbol : [u8;8], detail : u8) -> ShortSalePriceTestStatus
+    pub fn from(price_status : PriceStatus, timestamp : DateTime<Utc>, symbol : Symbol, detail : ShortSaleDetail) -> ShortSalePriceTestStatus
     {
         ShortSalePriceTestStatus { __t: IEXMessageType::ShortSalePriceTestStatus as u8, 
-                                   price_status: price_status, 
-                                   timestamp: timestamp, 
-                                   symbol: symbol, 
-                                   detail: detail }
+                                   price_status, 
+                                   timestamp, 
+                                   symbol, 
+                                   detail }
+    }
+}
+
+// Reg SHO short sale price test detail
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(from = "u8", into = "u8")]
+pub enum ShortSaleDetail {
+    NoPriceTest,
+    ActivatedIntradayPriceDrop,
+    ContinuedFromPriorDay,
+    Deactivated,
+    NotAvailable,
+    Other(u8),
+}
+
+impl ShortSaleDetail {
+    pub fn code(self) -> u8 {
+        self.into()
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            ShortSaleDetail::NoPriceTest => "No price test in place",
+            ShortSaleDetail::ActivatedIntradayPriceDrop => {
+                "Short sale price test restriction in effect due to an intraday price drop in the security"
+            }
+            ShortSaleDetail::ContinuedFromPriorDay => {
+                "Short sale price test restriction remains in effect from prior day"
+            }
+            ShortSaleDetail::Deactivated => "Short sale price test restriction deactivated",
+            ShortSaleDetail::NotAvailable => "Detail not available",
+            ShortSaleDetail::Other(_) => "Unknown detail",
+        }
+    }
+}
+
+impl From<u8> for ShortSaleDetail {
+    fn from(code: u8) -> ShortSaleDetail {
+        match code {
+            b' ' => ShortSaleDetail::NoPriceTest,
+            b'A' => ShortSaleDetail::ActivatedIntradayPriceDrop,
+            b'C' => ShortSaleDetail::ContinuedFromPriorDay,
+            b'D' => ShortSaleDetail::Deactivated,
+            b'N' => ShortSaleDetail::NotAvailable,
+            other => ShortSaleDetail::Other(other),
+        }
+    }
+}
+
+impl From<ShortSaleDetail> for u8 {
+    fn from(detail: ShortSaleDetail) -> u8 {
+        match detail {
+            ShortSaleDetail::NoPriceTest => b' ',
+            ShortSaleDetail::ActivatedIntradayPriceDrop => b'A',
+            ShortSaleDetail::ContinuedFromPriorDay => b'C',
+            ShortSaleDetail::Deactivated => b'D',
+            ShortSaleDetail::NotAvailable => b'N',
+            ShortSaleDetail::Other(code) => code,
+        }
+    }
+}
+
+impl fmt::Display for ShortSaleDetail {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.description())
     }
 }
 
 ///////////// Trading Status ///////////////
-#[derive(Deserialize_repr, Serialize_repr, Debug, PartialEq)]
+#[derive(Deserialize_repr, Serialize_repr, Debug, PartialEq, Clone, Copy)]
 #[repr(u8)]
 pub enum TradingStatus {
     Halt = 0x48,
@@ -257,26 +688,136 @@ pub enum TradingStatus {
     Unknown,
 }
 
+// Reason of a halt, pause or order acceptance period, 4 space padded characters
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(from = "[u8; 4]", into = "[u8; 4]")]
+pub enum TradingStatusReason {
+    // Sent with a trading status, when there is no reason
+    NoReason,
+    // Trading halted
+    HaltNewsPending,
+    IpoNotYetTrading,
+    IpoDeferred,
+    MarketWideCircuitBreakerLevel3,
+    NotAvailable,
+    // Order acceptance period
+    HaltNewsDissemination,
+    IpoOrderAcceptancePeriod,
+    IpoPreLaunchPeriod,
+    MarketWideCircuitBreakerLevel1,
+    MarketWideCircuitBreakerLevel2,
+    Other([u8; 4]),
+}
+
+impl TradingStatusReason {
+    const CODES: [(TradingStatusReason, &'static [u8; 4]); 11] = [
+        (TradingStatusReason::NoReason, b"    "),
+        (TradingStatusReason::HaltNewsPending, b"T1  "),
+        (TradingStatusReason::IpoNotYetTrading, b"IPO1"),
+        (TradingStatusReason::IpoDeferred, b"IPOD"),
+        (TradingStatusReason::MarketWideCircuitBreakerLevel3, b"MCB3"),
+        (TradingStatusReason::NotAvailable, b"NA  "),
+        (TradingStatusReason::HaltNewsDissemination, b"T2  "),
+        (TradingStatusReason::IpoOrderAcceptancePeriod, b"IPO2"),
+        (TradingStatusReason::IpoPreLaunchPeriod, b"IPO3"),
+        (TradingStatusReason::MarketWideCircuitBreakerLevel1, b"MCB1"),
+        (TradingStatusReason::MarketWideCircuitBreakerLevel2, b"MCB2"),
+    ];
+
+    // The padded code as sent by IEX
+    pub fn code(self) -> [u8; 4] {
+        self.into()
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            TradingStatusReason::NoReason => "No reason",
+            TradingStatusReason::HaltNewsPending => "Trading halt: news pending",
+            TradingStatusReason::IpoNotYetTrading => "IPO / new issue not yet trading",
+            TradingStatusReason::IpoDeferred => "IPO / new issue deferred",
+            TradingStatusReason::MarketWideCircuitBreakerLevel3 => {
+                "Market-wide circuit breaker level 3 breached"
+            }
+            TradingStatusReason::NotAvailable => "Reason not available",
+            TradingStatusReason::HaltNewsDissemination => "Halt news dissemination",
+            TradingStatusReason::IpoOrderAcceptancePeriod => {
+                "IPO / new issue order acceptance period"
+            }
+            TradingStatusReason::IpoPreLaunchPeriod => "IPO pre-launch period",
+            TradingStatusReason::MarketWideCircuitBreakerLevel1 => {
+                "Market-wide circuit breaker level 1 breached"
+            }
+            TradingStatusReason::MarketWideCircuitBreakerLevel2 => {
+                "Market-wide circuit breaker level 2 breached"
+            }
+            TradingStatusReason::Other(_) => "Unknown reason",
+        }
+    }
+}
+
+impl From<[u8; 4]> for TradingStatusReason {
+    fn from(code: [u8; 4]) -> TradingStatusReason {
+        TradingStatusReason::CODES
+            .iter()
+            .find(|(_, known)| **known == code)
+            .map(|(reason, _)| *reason)
+            .unwrap_or(TradingStatusReason::Other(code))
+    }
+}
+
+impl From<TradingStatusReason> for [u8; 4] {
+    fn from(reason: TradingStatusReason) -> [u8; 4] {
+        match reason {
+            TradingStatusReason::Other(code) => code,
+            known => TradingStatusReason::CODES
+                .iter()
+                .find(|(reason, _)| *reason == known)
+                .map(|(_, code)| **code)
+                .unwrap(),
+        }
+    }
+}
+
+impl fmt::Display for TradingStatusReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq)]
 pub struct TradingStatusMessage {
     pub(crate) __t: u8,
     pub trading_status: TradingStatus,
     #[serde(with = "ts_nanoseconds")]
     pub timestamp: DateTime<Utc>,
-    pub symbol: [u8; 8],
-    pub reason: [u8; 4],
+    pub symbol: Symbol,
+    pub reason: TradingStatusReason,
+}
+
+impl TradingStatusMessage {
+    pub fn from(
+        trading_status: TradingStatus,
+        timestamp: DateTime<Utc>,
+        symbol: Symbol,
+        reason: TradingStatusReason,
+    ) -> TradingStatusMessage {
+        TradingStatusMessage {
+            __t: IEXMessageType::TradingStatusMessage as u8,
+            trading_status,
+            timestamp,
+            symbol,
+            reason,
+        }
+    }
 }
 
 impl fmt::Debug for TradingStatusMessage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let symbol = String::from_utf8(self.symbol.to_vec()).unwrap();
-        let reason = String::from_utf8(self.reason.to_vec()).unwrap_or("NONE".to_string());
-
         f.debug_struct("TradingStatusMessage")
             .field("trading_status", &self.trading_status)
             .field("timestamp", &self.timestamp)
-            .field("symbol", &symbol.trim())
-            .field("reason", &reason.trim())
+            .field("symbol", &self.symbol)
+            .field("reason", &self.reason)
             .finish()
     }
 }
@@ -285,19 +826,40 @@ impl fmt::Debug for TradingStatusMessage {
 /////////// SECURITY DIRECTORY MESSAGE ////////
 /////////////////////////////////////////////// 
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct SecurityDirectoryMessage {
     __t: u8,
-    pub flags: u8,
+    pub flags: SecurityDirectoryFlags,
     #[serde(with = "ts_nanoseconds")]
     pub timestamp: DateTime<Utc>,
-    pub symbol: [u8; 8],
+    pub symbol: Symbol,
     pub round_lot_size: u32,
-    pub adjusted_poc_price: i64,
+    pub adjusted_poc_price: Price,
     pub luld_tier: LULDTier,
 }
 
-#[derive(Deserialize_repr, Debug, PartialEq)]
+impl SecurityDirectoryMessage {
+    pub fn from(
+        flags: SecurityDirectoryFlags,
+        timestamp: DateTime<Utc>,
+        symbol: Symbol,
+        round_lot_size: u32,
+        adjusted_poc_price: Price,
+        luld_tier: LULDTier,
+    ) -> SecurityDirectoryMessage {
+        SecurityDirectoryMessage {
+            __t: IEXMessageType::SecurityDirectoryMessage as u8,
+            flags,
+            timestamp,
+            symbol,
+            round_lot_size,
+            adjusted_poc_price,
+            luld_tier,
+        }
+    }
+}
+
+#[derive(Deserialize_repr, Serialize_repr, Debug, PartialEq)]
 #[repr(u8)]
 pub enum LULDTier {
     NotApplicable = 0x0,
@@ -305,32 +867,488 @@ pub enum LULDTier {
     Tier2NMS = 0x2,
 }
 
+/////////////////////////////////////////////// 
+/////////// SYSTEM EVENT MESSAGE //////////////
+/////////////////////////////////////////////// 
+
+#[derive(Deserialize_repr, Serialize_repr, Debug, PartialEq)]
+#[repr(u8)]
+pub enum SystemEvent {
+    StartOfMessages = 0x4f,
+    StartOfSystemHours = 0x53,
+    StartOfRegularMarketHours = 0x52,
+    EndOfRegularMarketHours = 0x4d,
+    EndOfSystemHours = 0x45,
+    EndOfMessages = 0x43,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct SystemEventMessage {
+    __t: u8,
+    pub system_event: SystemEvent,
+    #[serde(with = "ts_nanoseconds")]
+    pub timestamp: DateTime<Utc>,
+}
+
+impl SystemEventMessage {
+    pub fn from(system_event: SystemEvent, timestamp: DateTime<Utc>) -> SystemEventMessage {
+        SystemEventMessage {
+            __t: IEXMessageType::SystemEventMessage as u8,
+            system_event,
+            timestamp,
+        }
+    }
+}
+
+/////////////////////////////////////////////// 
+/////////// OFFICIAL PRICE MESSAGE ////////////
+/////////////////////////////////////////////// 
+
+#[derive(Deserialize_repr, Serialize_repr, Debug, PartialEq)]
+#[repr(u8)]
+pub enum OfficialPriceType {
+    OpeningPrice = 0x51,
+    ClosingPrice = 0x4d,
+}
+
+// IEX official opening and closing prices for IEX-listed securities
+#[derive(Deserialize, Serialize, PartialEq)]
+pub struct OfficialPriceMessage {
+    __t: u8,
+    pub price_type: OfficialPriceType,
+    #[serde(with = "ts_nanoseconds")]
+    pub timestamp: DateTime<Utc>,
+    pub symbol: Symbol,
+    pub official_price: Price,
+}
+
+impl OfficialPriceMessage {
+    pub fn from(
+        price_type: OfficialPriceType,
+        timestamp: DateTime<Utc>,
+        symbol: Symbol,
+        official_price: Price,
+    ) -> OfficialPriceMessage {
+        OfficialPriceMessage {
+            __t: IEXMessageType::OfficialPriceMessage as u8,
+            price_type,
+            timestamp,
+            symbol,
+            official_price,
+        }
+    }
+}
+
+impl fmt::Debug for OfficialPriceMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OfficialPriceMessage")
+            .field("price type", &self.price_type)
+            .field("timestamp", &self.timestamp)
+            .field("symbol", &self.symbol)
+            .field("official price", &self.official_price)
+            .finish()
+    }
+}
+
+/////////////////////////////////////////////// 
+/////////// OPERATIONAL HALT MESSAGE //////////
+/////////////////////////////////////////////// 
+
+#[derive(Deserialize_repr, Serialize_repr, Debug, PartialEq)]
+#[repr(u8)]
+pub enum OperationalHaltStatus {
+    Halted = 0x4f,
+    NotHalted = 0x4e,
+}
+
+// IEX specific operational trading halt, independent of the listing market halts
+#[derive(Deserialize, Serialize, PartialEq)]
+pub struct OperationalHaltMessage {
+    __t: u8,
+    pub halt_status: OperationalHaltStatus,
+    #[serde(with = "ts_nanoseconds")]
+    pub timestamp: DateTime<Utc>,
+    pub symbol: Symbol,
+}
+
+impl OperationalHaltMessage {
+    pub fn from(
+        halt_status: OperationalHaltStatus,
+        timestamp: DateTime<Utc>,
+        symbol: Symbol,
+    ) -> OperationalHaltMessage {
+        OperationalHaltMessage {
+            __t: IEXMessageType::OperationalHaltMessage as u8,
+            halt_status,
+            timestamp,
+            symbol,
+        }
+    }
+}
+
+impl fmt::Debug for OperationalHaltMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OperationalHaltMessage")
+            .field("halt status", &self.halt_status)
+            .field("timestamp", &self.timestamp)
+            .field("symbol", &self.symbol)
+            .finish()
+    }
+}
+
+/////////////////////////////////////////////// 
+/////////// DEEP PRICE LEVEL UPDATE ///////////
+/////////////////////////////////////////////// 
+
+#[derive(Deserialize_repr, Serialize_repr, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[repr(u8)]
+pub enum Side {
+    Buy = 0x38,
+    Sell = 0x35,
+}
+
+#[derive(Deserialize_repr, Serialize_repr, Debug, PartialEq, Clone, Copy)]
+#[repr(u8)]
+pub enum PriceLevelEventFlags {
+    // The order book is in the middle of an atomic transaction
+    OrderBookProcessingEvent = 0x0,
+    // The transaction is complete and the order book is consistent
+    EventProcessingComplete = 0x1,
+}
+
+// DEEP aggregated size of the resting displayed orders at a price level
+#[derive(Deserialize, Serialize, PartialEq)]
+pub struct PriceLevelUpdateMessage {
+    __t: u8,
+    pub event_flags: PriceLevelEventFlags,
+    #[serde(with = "ts_nanoseconds")]
+    pub timestamp: DateTime<Utc>,
+    pub symbol: Symbol,
+    pub size: u32,
+    pub price: Price,
+}
+
+impl PriceLevelUpdateMessage {
+    pub fn from(
+        side: Side,
+        event_flags: PriceLevelEventFlags,
+        timestamp: DateTime<Utc>,
+        symbol: Symbol,
+        size: u32,
+        price: Price,
+    ) -> PriceLevelUpdateMessage {
+        let t = match side {
+            Side::Buy => IEXMessageType::PriceLevelUpdateBuy,
+            Side::Sell => IEXMessageType::PriceLevelUpdateSell,
+        };
+        PriceLevelUpdateMessage {
+            __t: t as u8,
+            event_flags,
+            timestamp,
+            symbol,
+            size,
+            price,
+        }
+    }
+
+    pub fn side(&self) -> Side {
+        if self.__t == IEXMessageType::PriceLevelUpdateBuy as u8 {
+            Side::Buy
+        } else {
+            Side::Sell
+        }
+    }
+
+    // True when the update closes an atomic order book transaction
+    pub fn is_event_complete(&self) -> bool {
+        self.event_flags == PriceLevelEventFlags::EventProcessingComplete
+    }
+}
+
+impl fmt::Debug for PriceLevelUpdateMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PriceLevelUpdateMessage")
+            .field("side", &self.side())
+            .field("event flags", &self.event_flags)
+            .field("timestamp", &self.timestamp)
+            .field("symbol", &self.symbol)
+            .field("size", &self.size)
+            .field("price", &self.price)
+            .finish()
+    }
+}
+
+/////////////////////////////////////////////// 
+/////////// DEEP SECURITY EVENT ///////////////
+/////////////////////////////////////////////// 
+
+#[derive(Deserialize_repr, Serialize_repr, Debug, PartialEq)]
+#[repr(u8)]
+pub enum SecurityEvent {
+    OpeningProcessComplete = 0x4f,
+    ClosingProcessComplete = 0x43,
+}
+
+#[derive(Deserialize, Serialize, PartialEq)]
+pub struct SecurityEventMessage {
+    __t: u8,
+    pub security_event: SecurityEvent,
+    #[serde(with = "ts_nanoseconds")]
+    pub timestamp: DateTime<Utc>,
+    pub symbol: Symbol,
+}
+
+impl SecurityEventMessage {
+    pub fn from(
+        security_event: SecurityEvent,
+        timestamp: DateTime<Utc>,
+        symbol: Symbol,
+    ) -> SecurityEventMessage {
+        SecurityEventMessage {
+            __t: IEXMessageType::SecurityEventMessage as u8,
+            security_event,
+            timestamp,
+            symbol,
+        }
+    }
+}
+
+impl fmt::Debug for SecurityEventMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecurityEventMessage")
+            .field("security event", &self.security_event)
+            .field("timestamp", &self.timestamp)
+            .field("symbol", &self.symbol)
+            .finish()
+    }
+}
+
+/////////////////////////////////////////////// 
+/////////// DEEP+ ORDER MESSAGES //////////////
+/////////////////////////////////////////////// 
+
+// A new displayed order resting on the IEX order book
+#[derive(Deserialize, Serialize, PartialEq)]
+pub struct AddOrderMessage {
+    __t: u8,
+    pub side: Side,
+    #[serde(with = "ts_nanoseconds")]
+    pub timestamp: DateTime<Utc>,
+    pub symbol: Symbol,
+    pub order_id: u64,
+    pub size: u32,
+    pub price: Price,
+}
+
+impl AddOrderMessage {
+    pub fn from(
+        side: Side,
+        timestamp: DateTime<Utc>,
+        symbol: Symbol,
+        order_id: u64,
+        size: u32,
+        price: Price,
+    ) -> AddOrderMessage {
+        AddOrderMessage {
+            __t: IEXMessageType::AddOrderMessage as u8,
+            side,
+            timestamp,
+            symbol,
+            order_id,
+            size,
+            price,
+        }
+    }
+}
+
+impl fmt::Debug for AddOrderMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AddOrderMessage")
+            .field("side", &self.side)
+            .field("timestamp", &self.timestamp)
+            .field("symbol", &self.symbol)
+            .field("order id", &self.order_id)
+            .field("size", &self.size)
+            .field("price", &self.price)
+            .finish()
+    }
+}
+
+// Size and/or price change of a resting order
+#[derive(Deserialize, Serialize, PartialEq)]
+pub struct OrderModifyMessage {
+    __t: u8,
+    pub modify_flags: u8,
+    #[serde(with = "ts_nanoseconds")]
+    pub timestamp: DateTime<Utc>,
+    pub symbol: Symbol,
+    pub order_id: u64,
+    pub size: u32,
+    pub price: Price,
+}
+
+impl OrderModifyMessage {
+    pub fn from(
+        modify_flags: u8,
+        timestamp: DateTime<Utc>,
+        symbol: Symbol,
+        order_id: u64,
+        size: u32,
+        price: Price,
+    ) -> OrderModifyMessage {
+        OrderModifyMessage {
+            __t: IEXMessageType::OrderModifyMessage as u8,
+            modify_flags,
+            timestamp,
+            symbol,
+            order_id,
+            size,
+            price,
+        }
+    }
+
+    // Bit 0 of the modify flags is set when the order keeps its time priority
+    pub fn maintains_priority(&self) -> bool {
+        self.modify_flags & 0x01 != 0
+    }
+}
+
+impl fmt::Debug for OrderModifyMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OrderModifyMessage")
+            .field("modify flags", &self.modify_flags)
+            .field("timestamp", &self.timestamp)
+            .field("symbol", &self.symbol)
+            .field("order id", &self.order_id)
+            .field("size", &self.size)
+            .field("price", &self.price)
+            .finish()
+    }
+}
+
+// A resting order removed from the order book
+#[derive(Deserialize, Serialize, PartialEq)]
+pub struct OrderDeleteMessage {
+    __t: u8,
+    __reserved: u8,
+    #[serde(with = "ts_nanoseconds")]
+    pub timestamp: DateTime<Utc>,
+    pub symbol: Symbol,
+    pub order_id: u64,
+}
+
+impl OrderDeleteMessage {
+    pub fn from(timestamp: DateTime<Utc>, symbol: Symbol, order_id: u64) -> OrderDeleteMessage {
+        OrderDeleteMessage {
+            __t: IEXMessageType::OrderDeleteMessage as u8,
+            __reserved: 0,
+            timestamp,
+            symbol,
+            order_id,
+        }
+    }
+}
+
+impl fmt::Debug for OrderDeleteMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OrderDeleteMessage")
+            .field("timestamp", &self.timestamp)
+            .field("symbol", &self.symbol)
+            .field("order id", &self.order_id)
+            .finish()
+    }
+}
+
+// Execution against a resting order
+#[derive(Deserialize, Serialize, PartialEq)]
+pub struct OrderExecutedMessage {
+    __t: u8,
+    pub sale_condition_flags: SaleConditionFlags,
+    #[serde(with = "ts_nanoseconds")]
+    pub timestamp: DateTime<Utc>,
+    pub symbol: Symbol,
+    pub order_id: u64,
+    pub size: u32,
+    pub price: Price,
+    pub trade_id: u64,
+}
+
+impl OrderExecutedMessage {
+    pub fn from(
+        sale_condition_flags: SaleConditionFlags,
+        timestamp: DateTime<Utc>,
+        symbol: Symbol,
+        order_id: u64,
+        size: u32,
+        price: Price,
+        trade_id: u64,
+    ) -> OrderExecutedMessage {
+        OrderExecutedMessage {
+            __t: IEXMessageType::OrderExecutedMessage as u8,
+            sale_condition_flags,
+            timestamp,
+            symbol,
+            order_id,
+            size,
+            price,
+            trade_id,
+        }
+    }
+}
+
+impl fmt::Debug for OrderExecutedMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OrderExecutedMessage")
+            .field("sale condition flags", &self.sale_condition_flags)
+            .field("timestamp", &self.timestamp)
+            .field("symbol", &self.symbol)
+            .field("order id", &self.order_id)
+            .field("size", &self.size)
+            .field("price", &self.price)
+            .field("trade id", &self.trade_id)
+            .finish()
+    }
+}
+
 /////////////////////////////////////////////// 
 // Retail Indicator Message
 // TOPS broadcasts this message each time there is an update to IEX
 // eligible liquidity interest during the trading day
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, PartialEq)]
 pub struct RetailLiquidityIndicatorMessage {
     __t: u8,
     pub retail_liquidity_indicator: RetailLiquidityIndicator,
     #[serde(with = "ts_nanoseconds")]
     pub timestamp: DateTime<Utc>,
-    pub symbol: [u8; 8],
+    pub symbol: Symbol,
+}
+
+impl RetailLiquidityIndicatorMessage {
+    pub fn from(
+        retail_liquidity_indicator: RetailLiquidityIndicator,
+        timestamp: DateTime<Utc>,
+        symbol: Symbol,
+    ) -> RetailLiquidityIndicatorMessage {
+        RetailLiquidityIndicatorMessage {
+            __t: IEXMessageType::RetailLiquidityIndicator as u8,
+            retail_liquidity_indicator,
+            timestamp,
+            symbol,
+        }
+    }
 }
 
 impl fmt::Debug for RetailLiquidityIndicatorMessage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let symbol = String::from_utf8(self.symbol.to_vec()).unwrap();
-
         f.debug_struct("RetailLiquidityIndicatorMessage")
             .field("indicator", &self.retail_liquidity_indicator)
             .field("timestamp", &self.timestamp)
-            .field("symbol", &symbol.trim())
+            .field("symbol", &self.symbol)
             .finish()
     }
 }
 
-#[derive(Deserialize_repr, Serialize_repr, Debug, PartialEq)]
+#[derive(Deserialize_repr, Serialize_repr, Debug, PartialEq, Clone, Copy)]
 #[repr(u8)]
 pub enum RetailLiquidityIndicator {
     SPACE = 0x20,
@@ -339,3 +1357,19 @@ pub enum RetailLiquidityIndicator {
     BuySellInterest = 0x43,
     UNKNOWN,
 }
+
+impl RetailLiquidityIndicator {
+    pub fn has_buy_interest(&self) -> bool {
+        matches!(
+            self,
+            RetailLiquidityIndicator::BuyInterest | RetailLiquidityIndicator::BuySellInterest
+        )
+    }
+
+    pub fn has_sell_interest(&self) -> bool {
+        matches!(
+            self,
+            RetailLiquidityIndicator::SellInterest | RetailLiquidityIndicator::BuySellInterest
+        )
+    }
+}
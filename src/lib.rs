@@ -1,2 +1,17 @@
+pub mod compression;
+pub mod encoder;
+pub mod error;
+pub mod feedreader;
+pub mod feedwriter;
 pub mod iexdata;
+pub mod messageview;
+pub mod network;
+pub mod orderbook;
+pub mod packetfilter;
 pub mod packetprocessor;
+pub mod price;
+pub mod pricelevelbook;
+pub mod retailinterest;
+pub mod sequencetracker;
+pub mod symbol;
+pub mod topofbook;
@@ -0,0 +1,530 @@
+use crate::error::{DecodeError, DecodeErrorKind};
+use crate::iexdata::{
+    IEXProtocol, Price, PriceLevelEventFlags, QuoteFlags, SaleConditionFlags, Side, Symbol,
+};
+use crate::network::parse_ethernet_udp;
+use crate::packetprocessor::{
+    checked_slice, is_iex_tp, IEX_TP_HEADER_LENGTH, IEX_TP_VERSION, MESSAGE_LENGTH_SIZE,
+};
+
+// Message sizes, type byte included
+const QUOTE_UPDATE_LENGTH: usize = 42;
+const TRADE_REPORT_LENGTH: usize = 38;
+const PRICE_LEVEL_UPDATE_LENGTH: usize = 30;
+
+const QUOTE_UPDATE_TYPE: u8 = 0x51;
+const TRADE_REPORT_TYPE: u8 = 0x54;
+
+// Little endian readers; the view constructors check the buffer is long enough
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+fn read_i64(bytes: &[u8], offset: usize) -> i64 {
+    i64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+fn read_symbol(bytes: &[u8], offset: usize) -> Symbol {
+    Symbol::from_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// IEX-TP header read in place
+#[derive(Debug, Clone, Copy)]
+pub struct IEXHeaderView<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> IEXHeaderView<'a> {
+    pub fn version(&self) -> u8 {
+        self.bytes[0]
+    }
+
+    pub fn protocol_id(&self) -> u16 {
+        read_u16(self.bytes, 2)
+    }
+
+    pub fn channel_id(&self) -> u32 {
+        read_u32(self.bytes, 4)
+    }
+
+    pub fn session_id(&self) -> u32 {
+        read_u32(self.bytes, 8)
+    }
+
+    pub fn payload_length(&self) -> u16 {
+        read_u16(self.bytes, 12)
+    }
+
+    pub fn message_count(&self) -> u16 {
+        read_u16(self.bytes, 14)
+    }
+
+    pub fn stream_offset(&self) -> u64 {
+        read_u64(self.bytes, 16)
+    }
+
+    pub fn first_message_seq_number(&self) -> u64 {
+        read_u64(self.bytes, 24)
+    }
+
+    /// Nanoseconds since the epoch
+    pub fn send_time(&self) -> i64 {
+        read_i64(self.bytes, 32)
+    }
+}
+
+/// IEX-TP segment read in place, without copying nor allocating.
+///
+/// Only the header is checked when the view is built; the messages are
+/// located lazily by [`SegmentView::messages`].
+#[derive(Debug, Clone, Copy)]
+pub struct SegmentView<'a> {
+    bytes: &'a [u8],
+    protocol: IEXProtocol,
+}
+
+impl<'a> SegmentView<'a> {
+    pub fn new(segment: &'a [u8]) -> Result<SegmentView<'a>, DecodeError> {
+        let header = IEXHeaderView {
+            bytes: checked_slice(segment, 0, IEX_TP_HEADER_LENGTH)?,
+        };
+        let seq = header.first_message_seq_number();
+        if header.version() != IEX_TP_VERSION {
+            return Err(
+                DecodeError::new(DecodeErrorKind::BadVersion(header.version()), 0).with_packet(seq),
+            );
+        }
+        let protocol = IEXProtocol::from_id(header.protocol_id()).ok_or_else(|| {
+            DecodeError::new(
+                DecodeErrorKind::UnsupportedProtocol(header.protocol_id()),
+                0,
+            )
+            .with_packet(seq)
+        })?;
+        let payload_length = header.payload_length() as usize;
+        let bytes = checked_slice(segment, 0, IEX_TP_HEADER_LENGTH + payload_length)
+            .map_err(|e| e.with_packet(seq))?;
+        Ok(SegmentView { bytes, protocol })
+    }
+
+    /// View of the IEX-TP segment of an Ethernet frame (`PacketData::L2`),
+    /// `None` when the frame does not carry one
+    pub fn from_frame(frame: &'a [u8]) -> Result<Option<SegmentView<'a>>, DecodeError> {
+        match parse_ethernet_udp(frame)? {
+            Some(datagram) if is_iex_tp(&frame[datagram.start..datagram.end]) => {
+                SegmentView::new(&frame[datagram.start..datagram.end]).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    pub fn header(&self) -> IEXHeaderView<'a> {
+        IEXHeaderView {
+            bytes: &self.bytes[..IEX_TP_HEADER_LENGTH],
+        }
+    }
+
+    pub fn protocol(&self) -> IEXProtocol {
+        self.protocol
+    }
+
+    pub fn messages(&self) -> MessageIter<'a> {
+        MessageIter {
+            payload: &self.bytes[IEX_TP_HEADER_LENGTH..],
+            protocol: self.protocol,
+            first_message_seq_number: self.header().first_message_seq_number(),
+            message_count: self.header().message_count(),
+            index: 0,
+            start: 0,
+        }
+    }
+}
+
+/// Iterator over the messages of a segment. It stops after the first error.
+#[derive(Debug, Clone)]
+pub struct MessageIter<'a> {
+    payload: &'a [u8],
+    protocol: IEXProtocol,
+    first_message_seq_number: u64,
+    message_count: u16,
+    index: u16,
+    // Offset of the next message length in the payload
+    start: usize,
+}
+
+impl<'a> MessageIter<'a> {
+    fn next_message(&mut self) -> Result<MessageView<'a>, DecodeError> {
+        let (seq, index, start) = (self.first_message_seq_number, self.index, self.start);
+        let length_bytes = checked_slice(self.payload, start, MESSAGE_LENGTH_SIZE).map_err(|e| {
+            let mut e = e.with_packet(seq).with_message_index(index);
+            e.offset += IEX_TP_HEADER_LENGTH;
+            e
+        })?;
+        let length = u16::from_le_bytes([length_bytes[0], length_bytes[1]]) as usize;
+        // An empty message has no type byte
+        let msg_type = if length > 0 {
+            self.payload.get(start + MESSAGE_LENGTH_SIZE).copied()
+        } else {
+            None
+        };
+        let with_context = |e: DecodeError| {
+            let mut e = e.with_packet(seq).with_message_index(index);
+            if let Some(msg_type) = msg_type {
+                e = e.with_message(index, msg_type);
+            }
+            e.offset += IEX_TP_HEADER_LENGTH;
+            e
+        };
+        let bytes = self
+            .payload
+            .get((self.start + MESSAGE_LENGTH_SIZE)..(self.start + MESSAGE_LENGTH_SIZE + length))
+            .ok_or_else(|| {
+                with_context(DecodeError::new(
+                    DecodeErrorKind::LengthMismatch {
+                        expected: self.payload.len(),
+                        actual: self.start + MESSAGE_LENGTH_SIZE + length,
+                    },
+                    self.start,
+                ))
+            })?;
+        self.start += MESSAGE_LENGTH_SIZE + length;
+        Ok(MessageView {
+            bytes,
+            protocol: self.protocol,
+        })
+    }
+}
+
+impl<'a> Iterator for MessageIter<'a> {
+    type Item = Result<MessageView<'a>, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.message_count {
+            return None;
+        }
+        let result = self.next_message();
+        self.index = match result {
+            Ok(_) => self.index + 1,
+            Err(_) => self.message_count,
+        };
+        Some(result)
+    }
+}
+
+/// A message of a segment, starting at its type byte
+#[derive(Debug, Clone, Copy)]
+pub struct MessageView<'a> {
+    bytes: &'a [u8],
+    protocol: IEXProtocol,
+}
+
+impl<'a> MessageView<'a> {
+    pub fn msg_type(&self) -> Option<u8> {
+        self.bytes.first().copied()
+    }
+
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub fn quote_update(&self) -> Option<QuoteUpdateView<'a>> {
+        match self.msg_type() {
+            Some(QUOTE_UPDATE_TYPE)
+                if self.protocol == IEXProtocol::Tops
+                    && self.bytes.len() >= QUOTE_UPDATE_LENGTH =>
+            {
+                Some(QuoteUpdateView { bytes: self.bytes })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn trade_report(&self) -> Option<TradeReportView<'a>> {
+        match self.msg_type() {
+            Some(TRADE_REPORT_TYPE) if self.bytes.len() >= TRADE_REPORT_LENGTH => {
+                Some(TradeReportView { bytes: self.bytes })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn price_level_update(&self) -> Option<PriceLevelUpdateView<'a>> {
+        let side = match self.msg_type() {
+            Some(msg_type) if msg_type == Side::Buy as u8 => Side::Buy,
+            Some(msg_type) if msg_type == Side::Sell as u8 => Side::Sell,
+            _ => return None,
+        };
+        if self.protocol != IEXProtocol::Deep || self.bytes.len() < PRICE_LEVEL_UPDATE_LENGTH {
+            return None;
+        }
+        Some(PriceLevelUpdateView {
+            bytes: self.bytes,
+            side,
+        })
+    }
+}
+
+/// TOPS quote update read in place
+#[derive(Debug, Clone, Copy)]
+pub struct QuoteUpdateView<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> QuoteUpdateView<'a> {
+    pub fn flags(&self) -> QuoteFlags {
+        QuoteFlags::from_bits(self.bytes[1])
+    }
+
+    /// Nanoseconds since the epoch
+    pub fn timestamp(&self) -> i64 {
+        read_i64(self.bytes, 2)
+    }
+
+    pub fn symbol(&self) -> Symbol {
+        read_symbol(self.bytes, 10)
+    }
+
+    pub fn bid_size(&self) -> u32 {
+        read_u32(self.bytes, 18)
+    }
+
+    pub fn bid_price(&self) -> Price {
+        Price::from_raw(read_i64(self.bytes, 22))
+    }
+
+    pub fn ask_price(&self) -> Price {
+        Price::from_raw(read_i64(self.bytes, 30))
+    }
+
+    pub fn ask_size(&self) -> u32 {
+        read_u32(self.bytes, 38)
+    }
+}
+
+/// Trade report read in place
+#[derive(Debug, Clone, Copy)]
+pub struct TradeReportView<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> TradeReportView<'a> {
+    pub fn sale_condition_flags(&self) -> SaleConditionFlags {
+        SaleConditionFlags::from_bits(self.bytes[1])
+    }
+
+    /// Nanoseconds since the epoch
+    pub fn timestamp(&self) -> i64 {
+        read_i64(self.bytes, 2)
+    }
+
+    pub fn symbol(&self) -> Symbol {
+        read_symbol(self.bytes, 10)
+    }
+
+    pub fn size(&self) -> u32 {
+        read_u32(self.bytes, 18)
+    }
+
+    pub fn price(&self) -> Price {
+        Price::from_raw(read_i64(self.bytes, 22))
+    }
+
+    pub fn trade_id(&self) -> u64 {
+        read_u64(self.bytes, 30)
+    }
+}
+
+/// DEEP price level update read in place
+#[derive(Debug, Clone, Copy)]
+pub struct PriceLevelUpdateView<'a> {
+    bytes: &'a [u8],
+    side: Side,
+}
+
+impl<'a> PriceLevelUpdateView<'a> {
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    pub fn event_flags(&self) -> u8 {
+        self.bytes[1]
+    }
+
+    pub fn is_event_complete(&self) -> bool {
+        self.event_flags() == PriceLevelEventFlags::EventProcessingComplete as u8
+    }
+
+    /// Nanoseconds since the epoch
+    pub fn timestamp(&self) -> i64 {
+        read_i64(self.bytes, 2)
+    }
+
+    pub fn symbol(&self) -> Symbol {
+        read_symbol(self.bytes, 10)
+    }
+
+    pub fn size(&self) -> u32 {
+        read_u32(self.bytes, 18)
+    }
+
+    pub fn price(&self) -> Price {
+        Price::from_raw(read_i64(self.bytes, 22))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iexdata::*;
+    use crate::network::tests::make_frame;
+    use crate::packetprocessor::tests::{make_header, TRADING_STATUS_MESSAGE};
+    use crate::packetprocessor::{IEXPacketProcessor, PacketProcessor};
+
+    const TRADE_REPORT_MESSAGE: [u8; 40] = [
+        0x26, 0x00, 0x54, 0x00, 0xac, 0x63, 0xc0, 0x20, 0x96, 0x86, 0x6d, 0x14, 0x5a, 0x49, 0x45,
+        0x58, 0x54, 0x20, 0x20, 0x20, 0x64, 0x00, 0x00, 0x00, 0x24, 0x1d, 0x0f, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x96, 0x8f, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    fn quote_update_message() -> Vec<u8> {
+        let quote = QuoteUpdateMessage::from(
+            QuoteFlags::default(),
+            "2016-08-23T19:30:32.572715948Z".parse().unwrap(),
+            "ZIEXT".parse().unwrap(),
+            9700,
+            "99.05".parse().unwrap(),
+            "99.07".parse().unwrap(),
+            1000,
+        );
+        let bytes = bincode::serialize(&quote).unwrap();
+        [(bytes.len() as u16).to_le_bytes().to_vec(), bytes].concat()
+    }
+
+    fn make_segment() -> Vec<u8> {
+        let messages = [
+            quote_update_message(),
+            TRADE_REPORT_MESSAGE.to_vec(),
+            TRADING_STATUS_MESSAGE.to_vec(),
+        ]
+        .concat();
+        let header = bincode::serialize(&make_header(messages.len() as u16, 3)).unwrap();
+        [header, messages].concat()
+    }
+
+    #[test]
+    fn test_views_match_decoded_messages() {
+        let segment = make_segment();
+        let packet = IEXPacketProcessor {}.decode_segment(&segment).unwrap();
+        let view = SegmentView::new(&segment).unwrap();
+        let header = packet.header.unwrap();
+        assert_eq!(view.header().message_count(), 3);
+        assert_eq!(
+            view.header().first_message_seq_number(),
+            header.first_message_seq_number
+        );
+        assert_eq!(
+            view.header().send_time(),
+            header.send_time.timestamp_nanos()
+        );
+
+        let messages: Vec<MessageView> = view.messages().collect::<Result<_, _>>().unwrap();
+        assert_eq!(messages.len(), 3);
+        match &packet.payload[0] {
+            IEXMessage::QuoteUpdate(quote) => {
+                let quote_view = messages[0].quote_update().unwrap();
+                assert_eq!(quote_view.flags(), quote.flags);
+                assert_eq!(quote_view.timestamp(), quote.timestamp.timestamp_nanos());
+                assert_eq!(quote_view.symbol(), quote.symbol);
+                assert_eq!(quote_view.bid_size(), quote.bid_size);
+                assert_eq!(quote_view.bid_price(), quote.bid_price);
+                assert_eq!(quote_view.ask_price(), quote.ask_price);
+                assert_eq!(quote_view.ask_size(), quote.ask_size);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        match &packet.payload[1] {
+            IEXMessage::TradeReport(trade) => {
+                let trade_view = messages[1].trade_report().unwrap();
+                assert_eq!(trade_view.symbol(), trade.symbol);
+                assert_eq!(trade_view.size(), trade.size);
+                assert_eq!(trade_view.price(), trade.price);
+                assert_eq!(trade_view.trade_id(), trade.trade_id);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        assert!(messages[1].quote_update().is_none());
+        assert_eq!(messages[2].msg_type(), Some(0x48));
+        assert!(messages[2].trade_report().is_none());
+    }
+
+    #[test]
+    fn test_segment_view_from_frame() {
+        let frame = make_frame(&make_segment(), false, 0);
+        let view = SegmentView::from_frame(&frame).unwrap().unwrap();
+        assert_eq!(view.protocol(), IEXProtocol::Tops);
+        assert_eq!(view.messages().count(), 3);
+        assert!(SegmentView::from_frame(&make_frame(&[1, 2, 3], false, 0))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_truncated_message_stops_iteration() {
+        let mut segment = make_segment();
+        // Last message claims more bytes than the payload holds
+        let last = segment.len() - TRADING_STATUS_MESSAGE.len();
+        segment[last] = 0x30;
+        let view = SegmentView::new(&segment).unwrap();
+        let results: Vec<_> = view.messages().collect();
+        assert_eq!(results.len(), 3);
+        let error = results[2].as_ref().unwrap_err();
+        assert_eq!(error.message_index, Some(2));
+        assert_eq!(error.offset, last);
+        assert!(SegmentView::new(&segment[..50]).is_err());
+    }
+
+    #[test]
+    fn test_empty_message_has_no_type() {
+        // The byte after the payload is not taken for the type
+        let header = bincode::serialize(&make_header(2, 1)).unwrap();
+        let segment = [header, vec![0x00, 0x00, 0x48]].concat();
+        let view = SegmentView::new(&segment).unwrap();
+        let message = view.messages().next().unwrap().unwrap();
+        assert_eq!(message.msg_type(), None);
+        assert!(message.trade_report().is_none());
+    }
+
+    #[test]
+    fn test_price_level_update_view() {
+        let update = PriceLevelUpdateMessage::from(
+            Side::Sell,
+            PriceLevelEventFlags::EventProcessingComplete,
+            "2016-08-23T19:30:32.572715948Z".parse().unwrap(),
+            "ZIEXT".parse().unwrap(),
+            100,
+            Price::from_raw(990500),
+        );
+        let bytes = bincode::serialize(&update).unwrap();
+        let message = [(bytes.len() as u16).to_le_bytes().to_vec(), bytes].concat();
+        let mut header = make_header(message.len() as u16, 1);
+        header.protocol_id = IEXProtocol::Deep as u16;
+        let segment = [bincode::serialize(&header).unwrap(), message].concat();
+
+        let view = SegmentView::new(&segment).unwrap();
+        let message = view.messages().next().unwrap().unwrap();
+        let update_view = message.price_level_update().unwrap();
+        assert_eq!(update_view.side(), Side::Sell);
+        assert!(update_view.is_event_complete());
+        assert_eq!(update_view.size(), 100);
+        assert_eq!(update_view.price(), Price::from_raw(990500));
+        assert_eq!(update_view.symbol(), "ZIEXT");
+    }
+}
@@ -0,0 +1,322 @@
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+use pcap_parser::Linktype;
+
+use crate::error::{DecodeError, DecodeErrorKind};
+
+/// Linux cooked capture v2, not known by pcap-parser
+pub const LINKTYPE_LINUX_SLL2: Linktype = Linktype(276);
+pub const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+const ETHERNET_HEADER_LENGTH: usize = 14;
+const VLAN_TAG_LENGTH: usize = 4;
+const IPV4_MIN_HEADER_LENGTH: usize = 20;
+const UDP_HEADER_LENGTH: usize = 8;
+const SLL2_HEADER_LENGTH: usize = 20;
+pub const IP_PROTOCOL_UDP: u8 = 17;
+
+/// UDP payload located inside a captured frame, with its endpoints.
+/// `start..end` are the payload bounds in the frame bytes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct UdpDatagram {
+    pub source: SocketAddrV4,
+    pub destination: SocketAddrV4,
+    pub start: usize,
+    pub end: usize,
+}
+
+fn short_buffer(frame: &[u8], start: usize, needed: usize) -> DecodeError {
+    DecodeError::new(
+        DecodeErrorKind::ShortBuffer {
+            needed: Some(needed),
+            available: frame.len().saturating_sub(start),
+        },
+        start,
+    )
+}
+
+fn read_u16(frame: &[u8], start: usize) -> Result<u16, DecodeError> {
+    frame
+        .get(start..(start + 2))
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| short_buffer(frame, start, 2))
+}
+
+/// Parse the Ethernet header, skipping 802.1Q / 802.1ad VLAN tags.
+/// Returns the ethertype and the offset of the network layer.
+pub fn parse_ethernet(frame: &[u8]) -> Result<(u16, usize), DecodeError> {
+    if frame.len() < ETHERNET_HEADER_LENGTH {
+        return Err(short_buffer(frame, 0, ETHERNET_HEADER_LENGTH));
+    }
+    let mut start = ETHERNET_HEADER_LENGTH - 2;
+    let mut ethertype = read_u16(frame, start)?;
+    while ethertype == ETHERTYPE_VLAN || ethertype == ETHERTYPE_QINQ {
+        start += VLAN_TAG_LENGTH;
+        ethertype = read_u16(frame, start)?;
+    }
+    Ok((ethertype, start + 2))
+}
+
+/// Parse the IPv4 header at `start` and the UDP header following it.
+/// Returns `None` when the packet is not a complete UDP datagram
+/// (other transport protocol, IP fragment or packet cut by the capture).
+pub fn parse_ipv4_udp(frame: &[u8], start: usize) -> Result<Option<UdpDatagram>, DecodeError> {
+    let ip_header = match frame.get(start..(start + IPV4_MIN_HEADER_LENGTH)) {
+        Some(ip_header) => ip_header,
+        None => return Ok(None),
+    };
+    let version = ip_header[0] >> 4;
+    if version != 4 {
+        return Ok(None);
+    }
+    // The header length is in 32 bit words and includes the options
+    let header_length = ((ip_header[0] & 0x0f) as usize) * 4;
+    let total_length = u16::from_be_bytes([ip_header[2], ip_header[3]]) as usize;
+    let flags_fragment = u16::from_be_bytes([ip_header[6], ip_header[7]]);
+    let protocol = ip_header[9];
+    let source = Ipv4Addr::new(ip_header[12], ip_header[13], ip_header[14], ip_header[15]);
+    let destination = Ipv4Addr::new(ip_header[16], ip_header[17], ip_header[18], ip_header[19]);
+
+    // More fragments flag or non zero fragment offset
+    let is_fragment = flags_fragment & 0x3fff != 0;
+    if protocol != IP_PROTOCOL_UDP || is_fragment || header_length < IPV4_MIN_HEADER_LENGTH {
+        return Ok(None);
+    }
+
+    let udp_start = start + header_length;
+    let mut datagram = match parse_udp(frame, udp_start)? {
+        Some(datagram) => datagram,
+        None => return Ok(None),
+    };
+    let udp_length = datagram.end - udp_start;
+    if header_length + udp_length > total_length {
+        return Err(DecodeError::new(
+            DecodeErrorKind::LengthMismatch {
+                expected: total_length,
+                actual: header_length + udp_length,
+            },
+            udp_start,
+        ));
+    }
+    datagram.source.set_ip(source);
+    datagram.destination.set_ip(destination);
+    Ok(Some(datagram))
+}
+
+/// Parse the UDP header at `start`. The IP addresses of the returned
+/// datagram are unspecified, as they belong to the network layer.
+/// Returns `None` when the capture cut the datagram (snap length).
+pub fn parse_udp(frame: &[u8], start: usize) -> Result<Option<UdpDatagram>, DecodeError> {
+    let udp_header = match frame.get(start..(start + UDP_HEADER_LENGTH)) {
+        Some(udp_header) => udp_header,
+        None => return Ok(None),
+    };
+    let source_port = u16::from_be_bytes([udp_header[0], udp_header[1]]);
+    let destination_port = u16::from_be_bytes([udp_header[2], udp_header[3]]);
+    let udp_length = u16::from_be_bytes([udp_header[4], udp_header[5]]) as usize;
+    if udp_length < UDP_HEADER_LENGTH {
+        return Err(DecodeError::new(
+            DecodeErrorKind::LengthMismatch {
+                expected: UDP_HEADER_LENGTH,
+                actual: udp_length,
+            },
+            start,
+        ));
+    }
+
+    // The frame can be longer than the datagram because of the Ethernet padding
+    let end = start + udp_length;
+    if frame.len() < end {
+        return Ok(None);
+    }
+    Ok(Some(UdpDatagram {
+        source: SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, source_port),
+        destination: SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, destination_port),
+        start: start + UDP_HEADER_LENGTH,
+        end,
+    }))
+}
+
+/// Parse a Linux cooked capture v2 header.
+/// Returns the protocol type and the offset of the network layer.
+pub fn parse_linux_sll2(frame: &[u8]) -> Result<(u16, usize), DecodeError> {
+    if frame.len() < SLL2_HEADER_LENGTH {
+        return Err(short_buffer(frame, 0, SLL2_HEADER_LENGTH));
+    }
+    Ok((read_u16(frame, 0)?, SLL2_HEADER_LENGTH))
+}
+
+/// Locate the UDP payload of an Ethernet frame, `None` for non IPv4/UDP traffic
+/// and datagrams cut by the capture
+pub fn parse_ethernet_udp(frame: &[u8]) -> Result<Option<UdpDatagram>, DecodeError> {
+    let (ethertype, start) = parse_ethernet(frame)?;
+    if ethertype != ETHERTYPE_IPV4 {
+        return Ok(None);
+    }
+    parse_ipv4_udp(frame, start)
+}
+
+// Internet checksum of an IPv4 header whose checksum field is zero
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum: u32 = header
+        .chunks(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]) as u32)
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+// Multicast MAC address of an IPv4 group, a fixed locally administered one otherwise
+fn mac_address(ip: &Ipv4Addr) -> [u8; 6] {
+    let octets = ip.octets();
+    if ip.is_multicast() {
+        [0x01, 0x00, 0x5e, octets[1] & 0x7f, octets[2], octets[3]]
+    } else {
+        [0x02, 0x00, octets[0], octets[1], octets[2], octets[3]]
+    }
+}
+
+/// Wrap `payload` in Ethernet, IPv4 and UDP headers, the reverse of `parse_ethernet_udp`.
+/// `identification` is the IPv4 identification field. The UDP checksum is left
+/// out, which IPv4 allows. Returns `None` when the payload does not fit an IPv4 packet.
+pub fn build_ethernet_udp(
+    source: SocketAddrV4,
+    destination: SocketAddrV4,
+    identification: u16,
+    payload: &[u8],
+) -> Option<Vec<u8>> {
+    let udp_length = u16::try_from(UDP_HEADER_LENGTH + payload.len()).ok()?;
+    let total_length = udp_length.checked_add(IPV4_MIN_HEADER_LENGTH as u16)?;
+    let mut frame = Vec::with_capacity(ETHERNET_HEADER_LENGTH + total_length as usize);
+    frame.extend_from_slice(&mac_address(destination.ip()));
+    frame.extend_from_slice(&mac_address(source.ip()));
+    frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+
+    let ip_start = frame.len();
+    // Version 4, header of 5 words, don't fragment, TTL 64
+    frame.extend_from_slice(&[0x45, 0x00]);
+    frame.extend_from_slice(&total_length.to_be_bytes());
+    frame.extend_from_slice(&identification.to_be_bytes());
+    frame.extend_from_slice(&[0x40, 0x00, 0x40, IP_PROTOCOL_UDP, 0x00, 0x00]);
+    frame.extend_from_slice(&source.ip().octets());
+    frame.extend_from_slice(&destination.ip().octets());
+    let checksum = ipv4_checksum(&frame[ip_start..]);
+    frame[ip_start + 10..ip_start + 12].copy_from_slice(&checksum.to_be_bytes());
+
+    frame.extend_from_slice(&source.port().to_be_bytes());
+    frame.extend_from_slice(&destination.port().to_be_bytes());
+    frame.extend_from_slice(&udp_length.to_be_bytes());
+    frame.extend_from_slice(&[0x00, 0x00]);
+    frame.extend_from_slice(payload);
+    Some(frame)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // Wrap `payload` in Ethernet, optional VLAN tag, IPv4 (with options) and UDP headers
+    pub(crate) fn make_frame(payload: &[u8], vlan: bool, ip_options: usize) -> Vec<u8> {
+        let mut frame = vec![
+            0x01, 0x00, 0x5e, 0x57, 0x15, 0x04, 0x00, 0x1c, 0x73, 0x00, 0x00, 0x99,
+        ];
+        if vlan {
+            frame.extend_from_slice(&[0x81, 0x00, 0x00, 0x64]);
+        }
+        frame.extend_from_slice(&[0x08, 0x00]);
+        let header_length = IPV4_MIN_HEADER_LENGTH + ip_options;
+        let udp_length = UDP_HEADER_LENGTH + payload.len();
+        let total_length = (header_length + udp_length) as u16;
+        frame.push(0x40 | (header_length / 4) as u8);
+        frame.push(0x00);
+        frame.extend_from_slice(&total_length.to_be_bytes());
+        frame.extend_from_slice(&[0x00, 0x00, 0x40, 0x00, 0x40, IP_PROTOCOL_UDP, 0x00, 0x00]);
+        frame.extend_from_slice(&[10, 0, 0, 1, 233, 215, 21, 4]);
+        frame.extend(vec![0x01; ip_options]);
+        frame.extend_from_slice(&10378u16.to_be_bytes());
+        frame.extend_from_slice(&10378u16.to_be_bytes());
+        frame.extend_from_slice(&(udp_length as u16).to_be_bytes());
+        frame.extend_from_slice(&[0x00, 0x00]);
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[test]
+    fn test_parse_udp_frame() {
+        let frame = make_frame(&[1, 2, 3], false, 0);
+        let datagram = parse_ethernet_udp(&frame).unwrap().unwrap();
+        assert_eq!(datagram.source, "10.0.0.1:10378".parse().unwrap());
+        assert_eq!(datagram.destination, "233.215.21.4:10378".parse().unwrap());
+        assert_eq!(datagram.start, 42);
+        assert_eq!(&frame[datagram.start..datagram.end], &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_parse_vlan_frame_with_ip_options_and_padding() {
+        let mut frame = make_frame(&[1, 2, 3], true, 8);
+        frame.extend_from_slice(&[0; 10]);
+        let datagram = parse_ethernet_udp(&frame).unwrap().unwrap();
+        assert_eq!(datagram.start, 42 + 4 + 8);
+        assert_eq!(&frame[datagram.start..datagram.end], &[1, 2, 3]);
+    }
+
+    // Linux cooked capture v2 header followed by the IP packet of `make_frame`
+    pub(crate) fn make_sll2_frame(payload: &[u8]) -> Vec<u8> {
+        let frame = make_frame(payload, false, 0);
+        let header = vec![
+            0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x06, 0x00, 0x1c,
+            0x73, 0x00, 0x00, 0x99, 0x00, 0x00,
+        ];
+        [header, frame[ETHERNET_HEADER_LENGTH..].to_vec()].concat()
+    }
+
+    #[test]
+    fn test_parse_linux_sll2_frame() {
+        let frame = make_sll2_frame(&[1, 2, 3]);
+        let (protocol, start) = parse_linux_sll2(&frame).unwrap();
+        assert_eq!(protocol, ETHERTYPE_IPV4);
+        let datagram = parse_ipv4_udp(&frame, start).unwrap().unwrap();
+        assert_eq!(&frame[datagram.start..datagram.end], &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_build_udp_frame() {
+        let source = "10.0.0.1:10378".parse().unwrap();
+        let destination = "233.215.21.4:10378".parse().unwrap();
+        let frame = build_ethernet_udp(source, destination, 7, &[1, 2, 3]).unwrap();
+        assert_eq!(&frame[..6], &[0x01, 0x00, 0x5e, 0x57, 0x15, 0x04]);
+        let datagram = parse_ethernet_udp(&frame).unwrap().unwrap();
+        assert_eq!(datagram.source, source);
+        assert_eq!(datagram.destination, destination);
+        assert_eq!(&frame[datagram.start..datagram.end], &[1, 2, 3]);
+        // A valid header sums to zero with its checksum
+        assert_eq!(ipv4_checksum(&frame[14..34]), 0);
+        assert_eq!(build_ethernet_udp(source, destination, 7, &[0; 65508]), None);
+    }
+
+    #[test]
+    fn test_non_udp_traffic_is_skipped() {
+        let mut frame = make_frame(&[1, 2, 3], false, 0);
+        // TCP
+        frame[23] = 6;
+        assert_eq!(parse_ethernet_udp(&frame), Ok(None));
+        // ARP
+        frame[12] = 0x08;
+        frame[13] = 0x06;
+        assert_eq!(parse_ethernet_udp(&frame), Ok(None));
+        assert!(parse_ethernet_udp(&frame[..10]).is_err());
+    }
+
+    #[test]
+    fn test_frames_cut_by_the_capture_are_skipped() {
+        let frame = make_frame(&[1, 2, 3], false, 0);
+        // Inside the payload, the UDP header and the IPv4 header
+        for caplen in [frame.len() - 1, 40, 30] {
+            assert_eq!(parse_ethernet_udp(&frame[..caplen]), Ok(None));
+        }
+        assert_eq!(parse_udp(&frame[34..40], 0), Ok(None));
+    }
+}
@@ -0,0 +1,488 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+
+use crate::iexdata::*;
+use crate::packetprocessor::IEXPacket;
+use crate::pricelevelbook::{PriceLevel, PriceLevelSnapshot};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Order {
+    pub order_id: u64,
+    pub side: Side,
+    pub price: Price,
+    pub size: u32,
+    // Time the order got its current queue priority
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Inconsistency found while applying a DEEP+ message to the book
+#[derive(Debug, PartialEq, Eq)]
+pub enum OrderBookError {
+    /// An add order reuses the id of a resting order
+    DuplicateOrder(u64),
+    /// A modify, delete or execute refers to an order not in the book
+    UnknownOrder(u64),
+    /// An execution is larger than the resting size of the order
+    NegativeSize {
+        order_id: u64,
+        size: u32,
+        executed: u32,
+    },
+}
+
+impl fmt::Display for OrderBookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderBookError::DuplicateOrder(order_id) => {
+                write!(f, "order {} is already in the book", order_id)
+            }
+            OrderBookError::UnknownOrder(order_id) => write!(f, "unknown order {}", order_id),
+            OrderBookError::NegativeSize {
+                order_id,
+                size,
+                executed,
+            } => write!(
+                f,
+                "execution of {} shares on order {} with {} shares left",
+                executed, order_id, size
+            ),
+        }
+    }
+}
+
+impl Error for OrderBookError {}
+
+// Orders of a price level by arrival number, first in the queue first
+type Queue = BTreeMap<u64, u64>;
+
+#[derive(Debug)]
+struct RestingOrder {
+    order: Order,
+    // Arrival number of the order, its key in the queue of its price level
+    priority: u64,
+}
+
+/// Order by order book of a single symbol built from DEEP+ messages.
+///
+/// Each price level keeps its orders in time priority, so the queue ahead of
+/// any resting order can be inspected.
+#[derive(Debug, Default)]
+pub struct OrderBook {
+    orders: HashMap<u64, RestingOrder>,
+    bids: BTreeMap<Price, Queue>,
+    asks: BTreeMap<Price, Queue>,
+    next_priority: u64,
+}
+
+impl OrderBook {
+    pub fn new() -> OrderBook {
+        OrderBook::default()
+    }
+
+    fn levels_mut(&mut self, side: Side) -> &mut BTreeMap<Price, Queue> {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        }
+    }
+
+    fn levels(&self, side: Side) -> &BTreeMap<Price, Queue> {
+        match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        }
+    }
+
+    // Insert the order at the back of its price level queue
+    fn insert(&mut self, order: Order) {
+        let priority = self.next_priority;
+        self.next_priority += 1;
+        self.levels_mut(order.side)
+            .entry(order.price)
+            .or_default()
+            .insert(priority, order.order_id);
+        self.orders
+            .insert(order.order_id, RestingOrder { order, priority });
+    }
+
+    // Remove the order from the book and from its price level queue
+    fn remove(&mut self, order_id: u64) -> Option<Order> {
+        let RestingOrder { order, priority } = self.orders.remove(&order_id)?;
+        let levels = self.levels_mut(order.side);
+        if let Some(queue) = levels.get_mut(&order.price) {
+            queue.remove(&priority);
+            if queue.is_empty() {
+                levels.remove(&order.price);
+            }
+        }
+        Some(order)
+    }
+
+    pub fn add_order(&mut self, message: &AddOrderMessage) -> Result<(), OrderBookError> {
+        if self.orders.contains_key(&message.order_id) {
+            return Err(OrderBookError::DuplicateOrder(message.order_id));
+        }
+        self.insert(Order {
+            order_id: message.order_id,
+            side: message.side,
+            price: message.price,
+            size: message.size,
+            timestamp: message.timestamp,
+        });
+        Ok(())
+    }
+
+    pub fn modify_order(&mut self, message: &OrderModifyMessage) -> Result<(), OrderBookError> {
+        let order = &mut self
+            .orders
+            .get_mut(&message.order_id)
+            .ok_or(OrderBookError::UnknownOrder(message.order_id))?
+            .order;
+        // An order modified down to nothing leaves the book
+        if message.size == 0 {
+            self.remove(message.order_id);
+            return Ok(());
+        }
+        if message.maintains_priority() && order.price == message.price {
+            order.size = message.size;
+            return Ok(());
+        }
+
+        // The order loses its priority and goes to the back of the new level
+        let mut order = self.remove(message.order_id).unwrap();
+        order.price = message.price;
+        order.size = message.size;
+        order.timestamp = message.timestamp;
+        self.insert(order);
+        Ok(())
+    }
+
+    pub fn delete_order(&mut self, message: &OrderDeleteMessage) -> Result<(), OrderBookError> {
+        self.remove(message.order_id)
+            .map(|_| ())
+            .ok_or(OrderBookError::UnknownOrder(message.order_id))
+    }
+
+    pub fn execute_order(&mut self, message: &OrderExecutedMessage) -> Result<(), OrderBookError> {
+        let order = &mut self
+            .orders
+            .get_mut(&message.order_id)
+            .ok_or(OrderBookError::UnknownOrder(message.order_id))?
+            .order;
+        if message.size > order.size {
+            let size = order.size;
+            self.remove(message.order_id);
+            return Err(OrderBookError::NegativeSize {
+                order_id: message.order_id,
+                size,
+                executed: message.size,
+            });
+        }
+        order.size -= message.size;
+        if order.size == 0 {
+            self.remove(message.order_id);
+        }
+        Ok(())
+    }
+
+    /// Apply a DEEP+ order message; other messages are ignored
+    pub fn update(&mut self, message: &IEXMessage) -> Result<(), OrderBookError> {
+        match message {
+            IEXMessage::AddOrder(add) => self.add_order(add),
+            IEXMessage::OrderModify(modify) => self.modify_order(modify),
+            IEXMessage::OrderDelete(delete) => self.delete_order(delete),
+            IEXMessage::OrderExecuted(executed) => self.execute_order(executed),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn order(&self, order_id: u64) -> Option<&Order> {
+        self.orders.get(&order_id).map(|resting| &resting.order)
+    }
+
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    /// Orders resting at `price`, first in the queue first
+    pub fn queue(&self, side: Side, price: Price) -> impl Iterator<Item = &Order> {
+        self.levels(side)
+            .get(&price)
+            .into_iter()
+            .flat_map(|queue| queue.values())
+            .map(|order_id| &self.orders[order_id].order)
+    }
+
+    /// Number of shares queued ahead of the order at its price level
+    pub fn size_ahead(&self, order_id: u64) -> Option<u64> {
+        let order = self.order(order_id)?;
+        Some(
+            self.queue(order.side, order.price)
+                .take_while(|other| other.order_id != order_id)
+                .map(|other| other.size as u64)
+                .sum(),
+        )
+    }
+
+    fn aggregate<'a>(
+        &'a self,
+        levels: impl Iterator<Item = (&'a Price, &'a Queue)>,
+    ) -> Vec<PriceLevel> {
+        // Level sizes are u32 as in DEEP, so a deep level saturates
+        levels
+            .map(|(&price, queue)| PriceLevel {
+                price,
+                size: queue
+                    .values()
+                    .map(|order_id| self.orders[order_id].order.size)
+                    .fold(0u32, |total, size| total.saturating_add(size)),
+            })
+            .collect()
+    }
+
+    /// Aggregated view of the book, as a DEEP price level book would show it
+    pub fn price_levels(&self) -> PriceLevelSnapshot {
+        PriceLevelSnapshot {
+            bids: self.aggregate(self.bids.iter().rev()),
+            asks: self.aggregate(self.asks.iter()),
+        }
+    }
+}
+
+/// Order books of every symbol of a DEEP+ feed
+#[derive(Debug, Default)]
+pub struct OrderBooks {
+    books: HashMap<Symbol, OrderBook>,
+}
+
+impl OrderBooks {
+    pub fn new() -> OrderBooks {
+        OrderBooks::default()
+    }
+
+    /// Apply every message of the packet, returning the inconsistencies found
+    pub fn process_packet(&mut self, packet: &IEXPacket) -> Vec<OrderBookError> {
+        packet
+            .payload
+            .iter()
+            .filter_map(|message| self.update(message).err())
+            .collect()
+    }
+
+    pub fn update(&mut self, message: &IEXMessage) -> Result<(), OrderBookError> {
+        let symbol = match message {
+            IEXMessage::AddOrder(add) => add.symbol,
+            IEXMessage::OrderModify(modify) => modify.symbol,
+            IEXMessage::OrderDelete(delete) => delete.symbol,
+            IEXMessage::OrderExecuted(executed) => executed.symbol,
+            _ => return Ok(()),
+        };
+        self.books.entry(symbol).or_default().update(message)
+    }
+
+    pub fn get(&self, symbol: &Symbol) -> Option<&OrderBook> {
+        self.books.get(symbol)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Symbol, &OrderBook)> {
+        self.books.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packetprocessor::tests::{timestamp, SYMBOL};
+
+    fn add(side: Side, order_id: u64, size: u32, price: Price) -> IEXMessage {
+        IEXMessage::AddOrder(AddOrderMessage::from(
+            side,
+            timestamp(),
+            SYMBOL,
+            order_id,
+            size,
+            price,
+        ))
+    }
+
+    fn queue_ids(book: &OrderBook, side: Side, price: Price) -> Vec<u64> {
+        book.queue(side, price)
+            .map(|order| order.order_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_queue_priority() {
+        let mut book = OrderBook::new();
+        book.update(&add(Side::Buy, 1, 100, Price::from_raw(990500))).unwrap();
+        book.update(&add(Side::Buy, 2, 200, Price::from_raw(990500))).unwrap();
+        book.update(&add(Side::Buy, 3, 300, Price::from_raw(990500))).unwrap();
+        assert_eq!(queue_ids(&book, Side::Buy, Price::from_raw(990500)), vec![1, 2, 3]);
+        assert_eq!(book.size_ahead(3), Some(300));
+
+        // Keeping priority leaves the order in place
+        book.update(&IEXMessage::OrderModify(OrderModifyMessage::from(
+            0x01,
+            timestamp(),
+            SYMBOL,
+            1,
+            50,
+            Price::from_raw(990500),
+        )))
+        .unwrap();
+        assert_eq!(queue_ids(&book, Side::Buy, Price::from_raw(990500)), vec![1, 2, 3]);
+        assert_eq!(book.order(1).unwrap().size, 50);
+
+        // Losing priority sends it to the back of the queue
+        book.update(&IEXMessage::OrderModify(OrderModifyMessage::from(
+            0x00,
+            timestamp(),
+            SYMBOL,
+            1,
+            50,
+            Price::from_raw(990500),
+        )))
+        .unwrap();
+        assert_eq!(queue_ids(&book, Side::Buy, Price::from_raw(990500)), vec![2, 3, 1]);
+
+        book.update(&IEXMessage::OrderDelete(OrderDeleteMessage::from(
+            timestamp(),
+            SYMBOL,
+            2,
+        )))
+        .unwrap();
+        assert_eq!(queue_ids(&book, Side::Buy, Price::from_raw(990500)), vec![3, 1]);
+        assert_eq!(book.len(), 2);
+    }
+
+    #[test]
+    fn test_executions_and_price_levels() {
+        let mut book = OrderBook::new();
+        book.update(&add(Side::Buy, 1, 100, Price::from_raw(990500))).unwrap();
+        book.update(&add(Side::Buy, 2, 200, Price::from_raw(990400))).unwrap();
+        book.update(&add(Side::Sell, 3, 300, Price::from_raw(990700))).unwrap();
+        book.update(&add(Side::Sell, 4, 10, Price::from_raw(990700))).unwrap();
+
+        book.update(&IEXMessage::OrderExecuted(OrderExecutedMessage::from(
+            SaleConditionFlags::default(),
+            timestamp(),
+            SYMBOL,
+            3,
+            100,
+            Price::from_raw(990700),
+            1,
+        )))
+        .unwrap();
+        assert_eq!(book.order(3).unwrap().size, 200);
+
+        let levels = book.price_levels();
+        assert_eq!(
+            levels.bids,
+            vec![
+                PriceLevel {
+                    price: Price::from_raw(990500),
+                    size: 100
+                },
+                PriceLevel {
+                    price: Price::from_raw(990400),
+                    size: 200
+                }
+            ]
+        );
+        assert_eq!(
+            levels.asks,
+            vec![PriceLevel {
+                price: Price::from_raw(990700),
+                size: 210
+            }]
+        );
+
+        // A full execution removes the order
+        book.update(&IEXMessage::OrderExecuted(OrderExecutedMessage::from(
+            SaleConditionFlags::default(),
+            timestamp(),
+            SYMBOL,
+            1,
+            100,
+            Price::from_raw(990500),
+            2,
+        )))
+        .unwrap();
+        assert!(book.order(1).is_none());
+        assert_eq!(book.price_levels().bids.len(), 1);
+    }
+
+    #[test]
+    fn test_inconsistencies_are_reported() {
+        let mut books = OrderBooks::new();
+        books.update(&add(Side::Buy, 1, 100, Price::from_raw(990500))).unwrap();
+        assert_eq!(
+            books.update(&add(Side::Buy, 1, 100, Price::from_raw(990500))),
+            Err(OrderBookError::DuplicateOrder(1))
+        );
+        assert_eq!(
+            books.update(&IEXMessage::OrderExecuted(OrderExecutedMessage::from(
+                SaleConditionFlags::default(),
+                timestamp(),
+                SYMBOL,
+                7,
+                100,
+                Price::from_raw(990500),
+                1,
+            ))),
+            Err(OrderBookError::UnknownOrder(7))
+        );
+        assert_eq!(
+            books.update(&IEXMessage::OrderExecuted(OrderExecutedMessage::from(
+                SaleConditionFlags::default(),
+                timestamp(),
+                SYMBOL,
+                1,
+                150,
+                Price::from_raw(990500),
+                1,
+            ))),
+            Err(OrderBookError::NegativeSize {
+                order_id: 1,
+                size: 100,
+                executed: 150
+            })
+        );
+        assert!(books.get(&SYMBOL).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_modify_to_zero_removes_the_order() {
+        let mut book = OrderBook::new();
+        book.update(&add(Side::Sell, 1, 100, Price::from_raw(990700))).unwrap();
+        book.update(&add(Side::Sell, 2, 200, Price::from_raw(990700))).unwrap();
+        book.update(&IEXMessage::OrderModify(OrderModifyMessage::from(
+            0x01,
+            timestamp(),
+            SYMBOL,
+            1,
+            0,
+            Price::from_raw(990700),
+        )))
+        .unwrap();
+        assert!(book.order(1).is_none());
+        assert_eq!(queue_ids(&book, Side::Sell, Price::from_raw(990700)), vec![2]);
+        assert_eq!(book.size_ahead(2), Some(0));
+    }
+
+    #[test]
+    fn test_level_size_saturates() {
+        let mut book = OrderBook::new();
+        book.update(&add(Side::Buy, 1, u32::MAX, Price::from_raw(990500))).unwrap();
+        book.update(&add(Side::Buy, 2, 10, Price::from_raw(990500))).unwrap();
+        assert_eq!(book.price_levels().bids[0].size, u32::MAX);
+        assert_eq!(book.size_ahead(2), Some(u32::MAX as u64));
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+
+use crate::encoder::IexTpSegmentBuilder;
+use crate::error::EncodeError;
+use crate::iexdata::*;
+use crate::packetprocessor::IEXPacket;
+
+/// Selects messages by symbol and message type, and packets by send time.
+/// A criterion left unset matches everything.
+#[derive(Debug, Default, Clone)]
+pub struct MessageFilter {
+    symbols: Option<HashSet<Symbol>>,
+    msg_types: Option<HashSet<u8>>,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+}
+
+impl MessageFilter {
+    pub fn new() -> MessageFilter {
+        MessageFilter::default()
+    }
+
+    /// Keep the messages about these symbols. Messages without a symbol, as
+    /// system events and unknown messages, do not match.
+    pub fn symbols<I: IntoIterator<Item = Symbol>>(mut self, symbols: I) -> MessageFilter {
+        self.symbols = Some(symbols.into_iter().collect());
+        self
+    }
+
+    /// Keep the messages of these types, e.g. `b'Q'` for quote updates
+    pub fn message_types<I: IntoIterator<Item = u8>>(mut self, msg_types: I) -> MessageFilter {
+        self.msg_types = Some(msg_types.into_iter().collect());
+        self
+    }
+
+    /// Keep the packets sent in `[start, end)`
+    pub fn send_time_window(
+        mut self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> MessageFilter {
+        self.start = start;
+        self.end = end;
+        self
+    }
+
+    pub fn matches_packet(&self, packet: &IEXPacket) -> bool {
+        let send_time = match &packet.header {
+            Some(header) => header.send_time,
+            None => return false,
+        };
+        let after_start = match self.start {
+            Some(start) => send_time >= start,
+            None => true,
+        };
+        let before_end = match self.end {
+            Some(end) => send_time < end,
+            None => true,
+        };
+        after_start && before_end
+    }
+
+    pub fn matches_message(&self, message: &IEXMessage) -> bool {
+        let type_matches = match &self.msg_types {
+            Some(msg_types) => msg_types.contains(&message.msg_type()),
+            None => true,
+        };
+        let symbol_matches = match (&self.symbols, message.symbol()) {
+            (Some(symbols), Some(symbol)) => symbols.contains(&symbol),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        type_matches && symbol_matches
+    }
+}
+
+/// What happens to the sequence numbers of filtered packets
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SequenceMode {
+    /// Packets with a matching message are kept with all their messages and
+    /// header fields; dropped packets show as sequence gaps. Messages are
+    /// re-encoded when written, see `encode_packet`.
+    Original,
+    /// Matching messages are re-packed in new segments, numbered without gaps
+    /// from 1 in each stream with stream offsets to match
+    Resequenced,
+}
+
+/// Applies a `MessageFilter` to the packets of a feed
+#[derive(Debug)]
+pub struct PacketFilter {
+    filter: MessageFilter,
+    mode: SequenceMode,
+    // Re-packing state of every (channel_id, session_id) stream
+    builders: HashMap<(u32, u32), IexTpSegmentBuilder>,
+}
+
+impl PacketFilter {
+    pub fn new(filter: MessageFilter, mode: SequenceMode) -> PacketFilter {
+        PacketFilter {
+            filter,
+            mode,
+            builders: HashMap::new(),
+        }
+    }
+
+    pub fn mode(&self) -> SequenceMode {
+        self.mode
+    }
+
+    /// The packet to write in place of `packet`, `None` when nothing matches
+    pub fn apply(&mut self, mut packet: IEXPacket) -> Result<Option<IEXPacket>, EncodeError> {
+        if !self.filter.matches_packet(&packet) {
+            return Ok(None);
+        }
+        match self.mode {
+            SequenceMode::Original => {
+                let keep = packet
+                    .payload
+                    .iter()
+                    .any(|message| self.filter.matches_message(message));
+                Ok(if keep { Some(packet) } else { None })
+            }
+            SequenceMode::Resequenced => {
+                let filter = &self.filter;
+                packet
+                    .payload
+                    .retain(|message| filter.matches_message(message));
+                if packet.payload.is_empty() {
+                    return Ok(None);
+                }
+                let header = packet.header.as_ref().ok_or(EncodeError::MissingHeader)?;
+                let protocol = header
+                    .protocol()
+                    .ok_or(EncodeError::UnsupportedProtocol(header.protocol_id))?;
+                let builder = self
+                    .builders
+                    .entry((header.channel_id, header.session_id))
+                    .or_insert_with(|| {
+                        IexTpSegmentBuilder::new(protocol, header.session_id)
+                            .channel_id(header.channel_id)
+                    });
+                let send_time = header.send_time;
+                let result = packet
+                    .payload
+                    .iter()
+                    .try_for_each(|message| builder.push(message))
+                    .and_then(|()| {
+                        let header = builder.header(send_time);
+                        builder.finish(send_time).map(|_| header)
+                    });
+                match result {
+                    Ok(header) => {
+                        packet.header = Some(header);
+                        Ok(Some(packet))
+                    }
+                    Err(e) => {
+                        // The stream goes on without the messages of this packet
+                        builder.clear();
+                        Err(e)
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::feedreader::IexFeedReader;
+    use crate::feedwriter::{CaptureFormat, IexFeedWriter};
+    use crate::packetprocessor::tests::timestamp;
+    use crate::packetprocessor::{IEXPacketProcessor, PacketProcessor};
+
+    fn quote(symbol: &str) -> IEXMessage {
+        IEXMessage::QuoteUpdate(QuoteUpdateMessage::from(
+            QuoteFlags::default(),
+            timestamp(),
+            symbol.parse().unwrap(),
+            100,
+            "99.05".parse().unwrap(),
+            "99.07".parse().unwrap(),
+            200,
+        ))
+    }
+
+    fn trade(symbol: &str) -> IEXMessage {
+        IEXMessage::TradeReport(TradeReportMessage::from(
+            SaleConditionFlags::default(),
+            timestamp(),
+            symbol.parse().unwrap(),
+            100,
+            "99.06".parse().unwrap(),
+            1,
+        ))
+    }
+
+    // TOPS packets holding `messages`, sent one second apart
+    fn make_packets(messages: Vec<Vec<IEXMessage>>) -> Vec<IEXPacket> {
+        let mut builder =
+            IexTpSegmentBuilder::new(IEXProtocol::Tops, 1150681088).first_message_seq_number(10);
+        messages
+            .iter()
+            .enumerate()
+            .map(|(i, messages)| {
+                for message in messages {
+                    builder.push(message).unwrap();
+                }
+                let segment = builder
+                    .finish(timestamp() + Duration::seconds(i as i64))
+                    .unwrap();
+                IEXPacketProcessor {}.decode_segment(&segment).unwrap()
+            })
+            .collect()
+    }
+
+    fn system_event() -> IEXMessage {
+        IEXMessage::SystemEvent(SystemEventMessage::from(
+            SystemEvent::StartOfMessages,
+            timestamp(),
+        ))
+    }
+
+    #[test]
+    fn test_message_filter() {
+        let filter = MessageFilter::new()
+            .symbols(["AAPL".parse().unwrap()])
+            .message_types([b'Q', b'S']);
+        assert!(filter.matches_message(&quote("AAPL")));
+        assert!(!filter.matches_message(&quote("MSFT")));
+        assert!(!filter.matches_message(&trade("AAPL")));
+        // Messages without a symbol do not match a symbol filter
+        assert!(!filter.matches_message(&system_event()));
+        assert!(MessageFilter::new()
+            .message_types([b'S'])
+            .matches_message(&system_event()));
+        assert!(MessageFilter::new().matches_message(&trade("MSFT")));
+
+        let packets = make_packets(vec![vec![], vec![], vec![]]);
+        let filter = MessageFilter::new().send_time_window(
+            Some(timestamp() + Duration::seconds(1)),
+            Some(timestamp() + Duration::seconds(2)),
+        );
+        let matching: Vec<bool> = packets.iter().map(|p| filter.matches_packet(p)).collect();
+        assert_eq!(matching, vec![false, true, false]);
+    }
+
+    #[test]
+    fn test_original_sequence_keeps_whole_packets() {
+        let packets = make_packets(vec![
+            vec![quote("AAPL"), quote("MSFT")],
+            vec![quote("MSFT")],
+            vec![trade("AAPL")],
+        ]);
+        let mut filter = PacketFilter::new(
+            MessageFilter::new().symbols(["AAPL".parse().unwrap()]),
+            SequenceMode::Original,
+        );
+        let kept: Vec<IEXPacket> = packets
+            .into_iter()
+            .filter_map(|packet| filter.apply(packet).unwrap())
+            .collect();
+        assert_eq!(kept.len(), 2);
+        assert_eq!(kept[0].payload.len(), 2);
+        let seqs: Vec<u64> = kept
+            .iter()
+            .map(|p| p.header.as_ref().unwrap().first_message_seq_number)
+            .collect();
+        assert_eq!(seqs, vec![10, 13]);
+    }
+
+    #[test]
+    fn test_messages_without_symbol_do_not_keep_packets() {
+        let unknown = || IEXMessage::Unknown {
+            msg_type: 0x7a,
+            bytes: vec![0x7a, 0x01, 0x02],
+        };
+        let packets = make_packets(vec![
+            vec![unknown(), quote("MSFT")],
+            vec![system_event()],
+            vec![unknown(), quote("AAPL")],
+        ]);
+        let mut filter = PacketFilter::new(
+            MessageFilter::new().symbols(["AAPL".parse().unwrap()]),
+            SequenceMode::Original,
+        );
+        let kept: Vec<IEXPacket> = packets
+            .into_iter()
+            .filter_map(|packet| filter.apply(packet).unwrap())
+            .collect();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(
+            kept[0].header.as_ref().unwrap().first_message_seq_number,
+            13
+        );
+    }
+
+    #[test]
+    fn test_original_packets_are_written_with_their_headers() {
+        let packets = make_packets(vec![vec![quote("AAPL")], vec![trade("AAPL")]]);
+        let headers: Vec<IEXHeader> = packets.iter().map(|p| p.header.clone().unwrap()).collect();
+        let mut filter = PacketFilter::new(MessageFilter::new(), SequenceMode::Original);
+        let mut writer = IexFeedWriter::new(vec![], CaptureFormat::PcapNg).unwrap();
+        for packet in packets {
+            writer
+                .write_packet(&filter.apply(packet).unwrap().unwrap())
+                .unwrap();
+        }
+        let capture = writer.into_inner().unwrap();
+        let written: Vec<IEXPacket> = IexFeedReader::new(capture.as_slice())
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let written_headers: Vec<IEXHeader> =
+            written.into_iter().map(|p| p.header.unwrap()).collect();
+        assert_eq!(written_headers, headers);
+    }
+
+    #[test]
+    fn test_failed_packet_leaves_the_stream_unchanged() {
+        let mut packets = make_packets(vec![vec![quote("AAPL")], vec![quote("AAPL")]]);
+        // Not carried by TOPS: the packet cannot be re-packed
+        packets[0]
+            .payload
+            .push(IEXMessage::AddOrder(AddOrderMessage::from(
+                Side::Buy,
+                timestamp(),
+                "AAPL".parse().unwrap(),
+                1,
+                100,
+                "99.05".parse().unwrap(),
+            )));
+        let mut filter = PacketFilter::new(MessageFilter::new(), SequenceMode::Resequenced);
+        let mut packets = packets.into_iter();
+        assert!(filter.apply(packets.next().unwrap()).is_err());
+        let packet = filter.apply(packets.next().unwrap()).unwrap().unwrap();
+        let header = packet.header.unwrap();
+        assert_eq!(header.first_message_seq_number, 1);
+        assert_eq!(header.message_count, 1);
+        assert_eq!(header.stream_offset, 0);
+        assert_eq!(header.payload_length, 44);
+    }
+
+    #[test]
+    fn test_resequenced_packets_have_no_gaps() {
+        let packets = make_packets(vec![
+            vec![quote("AAPL"), quote("MSFT"), trade("AAPL")],
+            vec![quote("MSFT")],
+            vec![trade("MSFT"), quote("AAPL")],
+        ]);
+        let send_times: Vec<DateTime<Utc>> = packets
+            .iter()
+            .map(|p| p.header.as_ref().unwrap().send_time)
+            .collect();
+        let mut filter = PacketFilter::new(
+            MessageFilter::new().symbols(["AAPL".parse().unwrap()]),
+            SequenceMode::Resequenced,
+        );
+        let kept: Vec<IEXPacket> = packets
+            .into_iter()
+            .filter_map(|packet| filter.apply(packet).unwrap())
+            .collect();
+        assert_eq!(kept.len(), 2);
+        assert_eq!(kept[0].payload, vec![quote("AAPL"), trade("AAPL")]);
+        assert_eq!(kept[1].payload, vec![quote("AAPL")]);
+
+        let first = kept[0].header.as_ref().unwrap();
+        let second = kept[1].header.as_ref().unwrap();
+        assert_eq!(first.first_message_seq_number, 1);
+        assert_eq!(first.message_count, 2);
+        assert_eq!(first.stream_offset, 0);
+        assert_eq!(first.send_time, send_times[0]);
+        assert_eq!(second.first_message_seq_number, 3);
+        assert_eq!(second.stream_offset, first.payload_length as u64);
+        assert_eq!(second.send_time, send_times[2]);
+    }
+}
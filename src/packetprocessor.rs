@@ -1,28 +1,373 @@
-use std::any::Any;
 use std::fmt::Debug;
-use std::fmt::Display;
+use std::net::SocketAddrV4;
 
+use chrono::{DateTime, Duration, Utc};
+use log::{debug, log_enabled, trace, Level};
+
+use crate::error::{DecodeError, DecodeErrorKind};
 use crate::iexdata::*;
-use pcap_parser::data::PacketData;
+use crate::network::*;
+use pcap_parser::data::{get_packetdata, PacketData};
+use pcap_parser::Linktype;
+
+pub(crate) const IEX_TP_VERSION: u8 = 1;
+pub(crate) const IEX_TP_HEADER_LENGTH: usize = 40;
+pub(crate) const MESSAGE_LENGTH_SIZE: usize = 2;
+
+// Return the `len` bytes at `start`, or a short buffer error
+pub(crate) fn checked_slice(curr: &[u8], start: usize, len: usize) -> Result<&[u8], DecodeError> {
+    curr.get(start..(start + len)).ok_or_else(|| {
+        DecodeError::new(
+            DecodeErrorKind::ShortBuffer {
+                needed: Some(len),
+                available: curr.len().saturating_sub(start),
+            },
+            start,
+        )
+    })
+}
 
-fn deserialize_data<'a, T>(curr: &'a [u8], start: usize, message_data: &IEXMessageData) -> T
+fn deserialize_data<'a, T>(
+    curr: &'a [u8],
+    start: usize,
+    message_data: &IEXMessageData,
+) -> Result<T, DecodeError>
 where
     T: serde::de::Deserialize<'a>,
 {
     let total_size = message_data.length as usize;
-    println!("total_size : {0}", total_size);
-    let bytes_message = hex::encode(&curr[start..(start + total_size)]);
-    println!("bytes message : {0}", bytes_message);
-    let message: T = bincode::deserialize(&curr[start..(start + total_size)]).unwrap();
-    return message;
+    let message_bytes = checked_slice(curr, start, total_size)?;
+    if log_enabled!(Level::Trace) {
+        trace!("message bytes at {}: {}", start, hex::encode(message_bytes));
+    }
+    bincode::deserialize(message_bytes)
+        .map_err(|e| DecodeError::from_bincode(*e, start, total_size))
+}
+
+// True when the UDP payload looks like an IEX-TP segment of a known feed.
+// The version is not checked here so that the decoder reports a bad version.
+pub(crate) fn is_iex_tp(segment: &[u8]) -> bool {
+    segment.len() >= IEX_TP_HEADER_LENGTH
+        && IEXProtocol::from_id(u16::from_le_bytes([segment[2], segment[3]])).is_some()
+}
+
+// Decode the IEX-TP segment starting at `start`; `curr` must end with the segment
+fn decode_segment(curr: &[u8], mut start: usize) -> Result<IEXPacket, DecodeError> {
+    if log_enabled!(Level::Trace) {
+        trace!("segment: {}", hex::encode(&curr[start.min(curr.len())..]));
+    }
+
+    let mut return_packet = IEXPacket {
+        header: None,
+        payload: vec![],
+        source: None,
+        destination: None,
+        capture: None,
+    };
+    let header_bytes = checked_slice(curr, start, IEX_TP_HEADER_LENGTH)?;
+    let header: IEXHeader = bincode::deserialize(header_bytes)
+        .map_err(|e| DecodeError::from_bincode(*e, start, IEX_TP_HEADER_LENGTH))?;
+    debug!("header = {:?}", header);
+    let seq = header.first_message_seq_number;
+    if header.version != IEX_TP_VERSION {
+        return Err(
+            DecodeError::new(DecodeErrorKind::BadVersion(header.version), start)
+                .with_packet(seq),
+        );
+    }
+    let protocol = header.protocol().ok_or_else(|| {
+        DecodeError::new(
+            DecodeErrorKind::UnsupportedProtocol(header.protocol_id),
+            start,
+        )
+        .with_packet(seq)
+    })?;
+    start += IEX_TP_HEADER_LENGTH;
+    let message_count = header.message_count;
+    let mut total_byte_count = 0;
+    let payload_length = header.payload_length as usize;
+    checked_slice(curr, start, payload_length).map_err(|e| e.with_packet(seq))?;
+    return_packet.header = Some(header);
+    for index in 0..message_count {
+        let length_bytes = checked_slice(curr, start, MESSAGE_LENGTH_SIZE)
+            .map_err(|e| e.with_packet(seq).with_message_index(index))?;
+        let length = u16::from_le_bytes([length_bytes[0], length_bytes[1]]) as usize;
+        // The message type is the first byte after the length, an empty message has none
+        let msg_type = if length > 0 {
+            curr.get(start + MESSAGE_LENGTH_SIZE).copied()
+        } else {
+            None
+        };
+        let with_context = |e: DecodeError| {
+            let e = e.with_packet(seq).with_message_index(index);
+            match msg_type {
+                Some(msg_type) => e.with_message(index, msg_type),
+                None => e,
+            }
+        };
+        if total_byte_count + MESSAGE_LENGTH_SIZE + length > payload_length {
+            return Err(with_context(DecodeError::new(
+                DecodeErrorKind::LengthMismatch {
+                    expected: payload_length,
+                    actual: total_byte_count + MESSAGE_LENGTH_SIZE + length,
+                },
+                start,
+            )));
+        }
+
+        // Remove the message length from the count
+        start += MESSAGE_LENGTH_SIZE;
+
+        let known_type = msg_type.and_then(|msg_type| {
+            bincode::deserialize::<IEXMessageType>(&[msg_type]).ok()
+        });
+        let msg_type_enum = match known_type {
+            Some(msg_type_enum) => msg_type_enum,
+            None => {
+                // Message types we do not know are skipped, as mandated by the spec.
+                // An empty message is kept as an unknown one of type 0 without bytes.
+                let msg_type = msg_type.unwrap_or(0);
+                debug!("unknown message type 0x{:02x} at {}", msg_type, start);
+                return_packet.payload.push(IEXMessage::Unknown {
+                    msg_type,
+                    bytes: curr[start..(start + length)].to_vec(),
+                });
+                start += length;
+                total_byte_count += MESSAGE_LENGTH_SIZE + length;
+                continue;
+            }
+        };
+        let message_data = IEXMessageData {
+            length: length as u16,
+            msg_type: msg_type_enum,
+            msg_flags: if length > 1 { curr[start + 1] } else { 0 },
+        };
+        trace!("message_data = {:?}", message_data);
+
+        let single_packet: IEXMessage = match message_data.msg_type {
+            IEXMessageType::QuoteUpdateMessage if protocol == IEXProtocol::Tops => {
+                let quote =
+                    deserialize_data::<QuoteUpdateMessage>(curr, start, &message_data)
+                        .map_err(with_context)?;
+                IEXMessage::QuoteUpdate(quote)
+            }
+            IEXMessageType::ShortSalePriceTestStatus => {
+                let short_sale: ShortSalePriceTestStatus =
+                    deserialize_data::<ShortSalePriceTestStatus>(
+                        curr,
+                        start,
+                        &message_data,
+                    )
+                    .map_err(with_context)?;
+                IEXMessage::ShortSalePriceTestStatus(short_sale)
+            }
+            IEXMessageType::TradeReportMessage => {
+                let trade_report_message: TradeReportMessage =
+                    deserialize_data::<TradeReportMessage>(curr, start, &message_data)
+                        .map_err(with_context)?;
+                IEXMessage::TradeReport(trade_report_message)
+            }
+            IEXMessageType::TradingStatusMessage => {
+                let trading_status: TradingStatusMessage =
+                    deserialize_data::<TradingStatusMessage>(
+                        curr,
+                        start,
+                        &message_data,
+                    )
+                    .map_err(with_context)?;
+                IEXMessage::TradingStatus(trading_status)
+            }
+            IEXMessageType::SecurityDirectoryMessage => {
+                let security_dir: SecurityDirectoryMessage =
+                    deserialize_data::<SecurityDirectoryMessage>(
+                        curr,
+                        start,
+                        &message_data,
+                    )
+                    .map_err(with_context)?;
+                IEXMessage::SecurityDirectory(security_dir)
+            }
+            IEXMessageType::RetailLiquidityIndicator => {
+                let retail_indicator: RetailLiquidityIndicatorMessage =
+                    deserialize_data::<RetailLiquidityIndicatorMessage>(
+                        curr,
+                        start,
+                        &message_data,
+                    )
+                    .map_err(with_context)?;
+                IEXMessage::RetailLiquidityIndicator(retail_indicator)
+            }
+            IEXMessageType::AuctionInformationMessage => {
+                let auction_message: AuctionInformationMessage =
+                    deserialize_data::<AuctionInformationMessage>(
+                        curr,
+                        start,
+                        &message_data,
+                    )
+                    .map_err(with_context)?;
+                IEXMessage::AuctionInformation(auction_message)
+            }
+            IEXMessageType::SystemEventMessage => {
+                let system_event: SystemEventMessage =
+                    deserialize_data::<SystemEventMessage>(curr, start, &message_data)
+                        .map_err(with_context)?;
+                IEXMessage::SystemEvent(system_event)
+            }
+            IEXMessageType::TradeBreakMessage => {
+                let trade_break: TradeBreakMessage =
+                    deserialize_data::<TradeBreakMessage>(curr, start, &message_data)
+                        .map_err(with_context)?;
+                IEXMessage::TradeBreak(trade_break)
+            }
+            IEXMessageType::OfficialPriceMessage => {
+                let official_price: OfficialPriceMessage =
+                    deserialize_data::<OfficialPriceMessage>(
+                        curr,
+                        start,
+                        &message_data,
+                    )
+                    .map_err(with_context)?;
+                IEXMessage::OfficialPrice(official_price)
+            }
+            IEXMessageType::OperationalHaltMessage => {
+                let operational_halt: OperationalHaltMessage =
+                    deserialize_data::<OperationalHaltMessage>(
+                        curr,
+                        start,
+                        &message_data,
+                    )
+                    .map_err(with_context)?;
+                IEXMessage::OperationalHalt(operational_halt)
+            }
+            IEXMessageType::PriceLevelUpdateBuy if protocol == IEXProtocol::Deep => {
+                let price_level: PriceLevelUpdateMessage =
+                    deserialize_data::<PriceLevelUpdateMessage>(
+                        curr,
+                        start,
+                        &message_data,
+                    )
+                    .map_err(with_context)?;
+                IEXMessage::PriceLevelUpdateBuy(price_level)
+            }
+            IEXMessageType::PriceLevelUpdateSell if protocol == IEXProtocol::Deep => {
+                let price_level: PriceLevelUpdateMessage =
+                    deserialize_data::<PriceLevelUpdateMessage>(
+                        curr,
+                        start,
+                        &message_data,
+                    )
+                    .map_err(with_context)?;
+                IEXMessage::PriceLevelUpdateSell(price_level)
+            }
+            IEXMessageType::SecurityEventMessage
+                if matches!(protocol, IEXProtocol::Deep | IEXProtocol::DeepPlus) =>
+            {
+                let security_event: SecurityEventMessage =
+                    deserialize_data::<SecurityEventMessage>(
+                        curr,
+                        start,
+                        &message_data,
+                    )
+                    .map_err(with_context)?;
+                IEXMessage::SecurityEvent(security_event)
+            }
+            IEXMessageType::AddOrderMessage if protocol == IEXProtocol::DeepPlus => {
+                let add_order: AddOrderMessage =
+                    deserialize_data::<AddOrderMessage>(curr, start, &message_data)
+                        .map_err(with_context)?;
+                IEXMessage::AddOrder(add_order)
+            }
+            IEXMessageType::OrderModifyMessage if protocol == IEXProtocol::DeepPlus => {
+                let order_modify: OrderModifyMessage =
+                    deserialize_data::<OrderModifyMessage>(curr, start, &message_data)
+                        .map_err(with_context)?;
+                IEXMessage::OrderModify(order_modify)
+            }
+            IEXMessageType::OrderDeleteMessage if protocol == IEXProtocol::DeepPlus => {
+                let order_delete: OrderDeleteMessage =
+                    deserialize_data::<OrderDeleteMessage>(curr, start, &message_data)
+                        .map_err(with_context)?;
+                IEXMessage::OrderDelete(order_delete)
+            }
+            IEXMessageType::OrderExecutedMessage
+                if protocol == IEXProtocol::DeepPlus =>
+            {
+                let order_executed: OrderExecutedMessage =
+                    deserialize_data::<OrderExecutedMessage>(
+                        curr,
+                        start,
+                        &message_data,
+                    )
+                    .map_err(with_context)?;
+                IEXMessage::OrderExecuted(order_executed)
+            }
+            _ => {
+                debug!(
+                    "message type {:?} not expected on {:?}, kept as unknown",
+                    message_data.msg_type, protocol
+                );
+                let total_size = message_data.length as usize;
+                IEXMessage::Unknown {
+                    msg_type: curr[start],
+                    bytes: curr[start..(start + total_size)].to_vec(),
+                }
+            }
+        };
+
+        trace!("{:?}", single_packet);
+        return_packet.payload.push(single_packet);
+        start += length;
+        total_byte_count += MESSAGE_LENGTH_SIZE + length;
+    }
+
+    if total_byte_count != payload_length {
+        return Err(DecodeError::new(
+            DecodeErrorKind::LengthMismatch {
+                expected: payload_length,
+                actual: total_byte_count,
+            },
+            start,
+        )
+        .with_packet(seq));
+    }
+    Ok(return_packet)
 }
 
 pub trait PacketProcessor {
-    fn process_packet_data(
+    /// Decode the IEX-TP segment carried by a captured packet.
+    /// Packets that are not IEX-TP over UDP/IPv4 are skipped and yield `None`.
+    fn process_packet_data(&self, data: Option<PacketData>)
+        -> Result<Option<IEXPacket>, DecodeError>;
+
+    /// Decode the raw bytes of a captured packet according to its link type.
+    /// This also handles link types pcap-parser does not know, as Linux SLL2.
+    fn process_link_data(
         &self,
-        data: Option<PacketData>,
-        frame_header_length: usize,
-    ) -> IEXPacket;
+        data: &[u8],
+        linktype: Linktype,
+        caplen: usize,
+    ) -> Result<Option<IEXPacket>, DecodeError>;
+
+    /// Decode a bare IEX-TP segment, without any network header
+    fn decode_segment(&self, segment: &[u8]) -> Result<IEXPacket, DecodeError>;
+}
+
+// Decode the IEX-TP segment carried by the datagram found in `data`
+fn decode_datagram(
+    data: &[u8],
+    datagram: Option<UdpDatagram>,
+) -> Result<Option<IEXPacket>, DecodeError> {
+    let datagram = match datagram {
+        Some(datagram) => datagram,
+        None => return Ok(None),
+    };
+    if !is_iex_tp(&data[datagram.start..datagram.end]) {
+        return Ok(None);
+    }
+    let mut return_packet = decode_segment(&data[..datagram.end], datagram.start)?;
+    return_packet.source = Some(datagram.source);
+    return_packet.destination = Some(datagram.destination);
+    Ok(Some(return_packet))
 }
 
 pub struct IEXPacketProcessor {}
@@ -30,148 +375,101 @@ pub struct IEXPacketProcessor {}
 #[derive(Debug)]
 pub struct IEXPacket {
     pub header: Option<IEXHeader>,
-    pub payload: Vec<Box<dyn Any>>,
+    pub payload: Vec<IEXMessage>,
+    // UDP endpoints, when the segment was read from a captured frame
+    pub source: Option<SocketAddrV4>,
+    pub destination: Option<SocketAddrV4>,
+    // Where and when the packet was captured, when it was read from a capture file
+    pub capture: Option<CaptureMetadata>,
 }
 
-#[derive(Debug)]
-struct Dummy {}
+impl IEXPacket {
+    /// Time between the IEX send time and the capture of the packet
+    pub fn capture_latency(&self) -> Option<Duration> {
+        let timestamp = self.capture.as_ref()?.timestamp?;
+        Some(timestamp - self.header.as_ref()?.send_time)
+    }
+}
+
+/// Capture details of a packet read from a pcap or pcapng file
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CaptureMetadata {
+    /// Index of the pcap block holding the packet, counting from 0
+    pub block_index: u64,
+    pub linktype: Linktype,
+    /// pcapng interface the packet was captured on, `None` for legacy pcap
+    pub interface_id: Option<u32>,
+    /// Capture time, `None` for pcapng simple packet blocks which have none
+    pub timestamp: Option<DateTime<Utc>>,
+    pub captured_length: u32,
+    /// Length of the packet on the wire
+    pub original_length: u32,
+}
+
+impl CaptureMetadata {
+    // The capture kept only the first bytes of the packet (snap length)
+    pub fn is_truncated(&self) -> bool {
+        self.captured_length < self.original_length
+    }
+}
 
 impl PacketProcessor for IEXPacketProcessor {
     // process packet data
     fn process_packet_data(
         &self,
         data: Option<PacketData>,
-        frame_header_length: usize,
-    ) -> IEXPacket {
-        let packet = data.expect("Impossible to process packet");
-
-        let r: IEXPacket = match packet {
-            PacketData::L2(curr) => {
-                let x = hex::encode(curr);
-                println!("{:?}", x);
-                let mut start = 0;
-                start += frame_header_length;
-
-                let iex_message_header_length = 40;
-                let mut return_packet = IEXPacket {
-                    header: None,
-                    payload: vec![],
-                };
-                let header_bytes = &curr[start..(start + iex_message_header_length)];
-                let header: IEXHeader = bincode::deserialize(header_bytes).unwrap();
-                println!("header = {:?}", header);
-                start += iex_message_header_length;
-                let mut cnt = header.message_count;
-                println!("cnt : {0}", cnt);
-                let mut total_byte_count = 0;
-                let payload_length = header.payload_length;
-                return_packet.header = Some(header);
-                println!("start : {0}", start);
-                while cnt > 0 {
-                    let message_data: IEXMessageData =
-                        bincode::deserialize(&curr[start..(start + 4)]).unwrap();
-                    println!("message_data = {:?}", message_data);
-
-                    // Remove the message length from the count
-                    start += 2;
-
-                    let single_packet: Box<dyn Any> = match message_data.msg_type {
-                        IEXMessageType::QuoteUpdateMessage => {
-                            let quote =
-                                deserialize_data::<QuoteUpdateMessage>(&curr, start, &message_data);
-                            println!("quote : {:?}", quote);
-                            Box::new(quote)
-                        }
-                        IEXMessageType::ShortSalePriceTestStatus => {
-                            let short_sale: ShortSalePriceTestStatus =
-                                deserialize_data::<ShortSalePriceTestStatus>(
-                                    &curr,
-                                    start,
-                                    &message_data,
-                                );
-                            // println!("short sale message = {:?}", short_sale);
-                            Box::new(short_sale)
-                        }
-                        IEXMessageType::TradeReportMessage => {
-                            let trade_report_message: TradeReportMessage =
-                                deserialize_data::<TradeReportMessage>(&curr, start, &message_data);
-                            // println!("trading report message = {:?}", trade_report_message);
-                            Box::new(trade_report_message)
-                        }
-                        IEXMessageType::TradingStatusMessage => {
-                            let trading_status: TradingStatusMessage =
-                                deserialize_data::<TradingStatusMessage>(
-                                    &curr,
-                                    start,
-                                    &message_data,
-                                );
-                            // println!("trading status message = {:?}", trading_status);
-                            Box::new(trading_status)
-                        }
-                        IEXMessageType::SecurityDirectoryMessage => {
-                            let security_dir: SecurityDirectoryMessage =
-                                deserialize_data::<SecurityDirectoryMessage>(
-                                    &curr,
-                                    start,
-                                    &message_data,
-                                );
-                            // println!("security dir = {:?}", security_dir);
-                            Box::new(security_dir)
-                        }
-                        IEXMessageType::RetailLiquidityIndicator => {
-                            let retail_indicator: RetailLiquidityIndicator =
-                                deserialize_data::<RetailLiquidityIndicator>(
-                                    &curr,
-                                    start,
-                                    &message_data,
-                                );
-                            // println!("retail update  = {:?}", retail_indicator);
-                            Box::new(retail_indicator)
-                        }
-                        IEXMessageType::AuctionInformationMessage => {
-                            let auction_message: AuctionInformationMessage =
-                                deserialize_data::<AuctionInformationMessage>(
-                                    &curr,
-                                    start,
-                                    &message_data,
-                                );
-                            // println!("auction message  = {:?}", auction_message);
-                            Box::new(auction_message)
-                        }
-                        _ => {
-                            // println!("message not processed yet: nothing to do!");
-                            Box::new(Dummy {})
-                        }
-                    };
-
-                    println!("{:?}", single_packet);
-                    return_packet.payload.push(single_packet);
-                    start += message_data.length as usize;
-                    cnt -= 1;
-                    total_byte_count += 2 + message_data.length as usize;
-                }
+    ) -> Result<Option<IEXPacket>, DecodeError> {
+        let packet =
+            data.ok_or_else(|| DecodeError::new(DecodeErrorKind::MissingPacketData, 0))?;
 
-                // eprintln!("total count : {0}", total_byte_count);
-                assert_eq!(total_byte_count, payload_length as usize);
-                return_packet
+        match packet {
+            PacketData::L2(frame) => decode_datagram(frame, parse_ethernet_udp(frame)?),
+            PacketData::L3(ETHERTYPE_IPV4, ip_packet) => {
+                decode_datagram(ip_packet, parse_ipv4_udp(ip_packet, 0)?)
             }
-            PacketData::L3(_, _) | PacketData::L4(_, _) | PacketData::Unsupported(_) => todo!(),
-        };
+            // The IP addresses are not known at this layer, only the ports
+            PacketData::L4(IP_PROTOCOL_UDP, udp_datagram) => {
+                decode_datagram(udp_datagram, parse_udp(udp_datagram, 0)?)
+            }
+            PacketData::L3(_, _) | PacketData::L4(_, _) => Ok(None),
+            PacketData::Unsupported(_) => Err(DecodeError::new(
+                DecodeErrorKind::UnsupportedPacketData,
+                0,
+            )),
+        }
+    }
+
+    fn process_link_data(
+        &self,
+        data: &[u8],
+        linktype: Linktype,
+        caplen: usize,
+    ) -> Result<Option<IEXPacket>, DecodeError> {
+        if linktype == LINKTYPE_LINUX_SLL2 {
+            let data = &data[..caplen.min(data.len())];
+            return match parse_linux_sll2(data)? {
+                (ETHERTYPE_IPV4, start) => decode_datagram(data, parse_ipv4_udp(data, start)?),
+                _ => Ok(None),
+            };
+        }
+        self.process_packet_data(get_packetdata(data, linktype, caplen))
+    }
 
-        return r;
+    fn decode_segment(&self, segment: &[u8]) -> Result<IEXPacket, DecodeError> {
+        decode_segment(segment, 0)
     }
 }
 
 #[cfg(test)]
-mod tests {
-    use std::{any::Any, str::FromStr};
+pub(crate) mod tests {
+    use std::str::FromStr;
 
     use chrono::{DateTime, Utc};
 
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
 
-    const header_length : usize = 40;
+    const HEADER_LENGTH : usize = 40;
 
     #[test]
     fn test_can_read_trading_status() {
@@ -189,7 +487,7 @@ mod tests {
             send_time: Utc::now(),
         };
         let by = bincode::serialize(&test_header);
-        assert_eq!(by.is_ok(), true);
+        assert!(by.is_ok());
         assert_eq!(by.as_ref().unwrap().len(), 40);
 
         let raw_packet: Vec<u8> = vec![
@@ -198,17 +496,16 @@ mod tests {
         ];
 
         let res: Vec<u8> = [by.unwrap(), raw_packet].concat();
-        assert_eq!(res.len(), test_header.payload_length as usize + header_length);
-        let expected_packet = packet_processor.process_packet_data(Some(PacketData::L2(&res)), 0);
+        assert_eq!(res.len(), test_header.payload_length as usize + HEADER_LENGTH);
+        let expected_packet = packet_processor.decode_segment(&res).unwrap();
         let expected_message = TradingStatusMessage {
             trading_status: TradingStatus::Halt,
             timestamp: DateTime::<Utc>::from_str("2016-08-23T19:30:32.572715948Z").unwrap(),
-            symbol: [0x5a, 0x49, 0x45, 0x58, 0x54, 0x20, 0x20, 0x20], // ZIEXT
-            reason: [0x54, 0x31, 0x20, 0x20],
+            symbol: SYMBOL,
+            reason: TradingStatusReason::HaltNewsPending,
             __t: 0x48,
         };
-        let computed_message = expected_packet.payload[0].downcast_ref::<TradingStatusMessage>();
-        assert_eq!(&expected_message, computed_message.unwrap());
+        assert_eq!(expected_packet.payload[0], IEXMessage::TradingStatus(expected_message));
     }
 
     #[test]
@@ -228,8 +525,8 @@ mod tests {
         };
 
         let header_bytes = bincode::serialize(&test_header);
-        assert_eq!(header_bytes.is_ok(), true);
-        assert_eq!(header_bytes.as_ref().unwrap().len(), header_length);
+        assert!(header_bytes.is_ok());
+        assert_eq!(header_bytes.as_ref().unwrap().len(), HEADER_LENGTH);
 
         let raw_packet: Vec<u8> = vec![
             0x2A, 0x00, 0x51, 0x00, 0xac, 0x63, 0xc0, 0x20, 0x96, 0x86, 0x6d, 0x14, 0x5a, 0x49,
@@ -239,11 +536,10 @@ mod tests {
         ];
 
         let res: Vec<u8> = [header_bytes.unwrap(), raw_packet].concat();
-        assert_eq!(res.len(), test_header.payload_length as usize + header_length);
-        let expected_packet = packet_processor.process_packet_data(Some(PacketData::L2(&res)), 0);
-        let expected_message = QuoteUpdateMessage::from(0x00, DateTime::<Utc>::from_str("2016-08-23T19:30:32.572715948Z").unwrap(), [0x5a, 0x49, 0x45, 0x58, 0x54, 0x20, 0x20, 0x20], 9700, 99.05000000000001, 99.07000000000001, 1000);
-        let computed_message = expected_packet.payload[0].downcast_ref::<QuoteUpdateMessage>();
-        assert_eq!(&expected_message, computed_message.unwrap());
+        assert_eq!(res.len(), test_header.payload_length as usize + HEADER_LENGTH);
+        let expected_packet = packet_processor.decode_segment(&res).unwrap();
+        let expected_message = QuoteUpdateMessage::from(QuoteFlags::default(), timestamp(), SYMBOL, 9700, "99.05".parse().unwrap(), "99.07".parse().unwrap(), 1000);
+        assert_eq!(expected_packet.payload[0], IEXMessage::QuoteUpdate(expected_message));
     }
 
     #[test]
@@ -263,8 +559,8 @@ mod tests {
         };
 
         let header_bytes = bincode::serialize(&test_header);
-        assert_eq!(header_bytes.is_ok(), true);
-        assert_eq!(header_bytes.as_ref().unwrap().len(), header_length);
+        assert!(header_bytes.is_ok());
+        assert_eq!(header_bytes.as_ref().unwrap().len(), HEADER_LENGTH);
 
         let raw_packet: Vec<u8> = vec![
             0x13, 0x00, 0x50, 0x01, 
@@ -274,12 +570,572 @@ mod tests {
         ];
 
         let res: Vec<u8> = [header_bytes.unwrap(), raw_packet].concat();
-        assert_eq!(res.len(), test_header.payload_length as usize + header_length);
-        let expected_packet = packet_processor.process_packet_data(Some(PacketData::L2(&res)), 0);
-        let expected_message = ShortSalePriceTestStatus::from(PriceStatus::InEffect, DateTime::<Utc>::from_str("2016-08-23T19:30:32.572715948Z").unwrap(), [0x5a, 0x49, 0x45, 0x58, 0x54, 0x20, 0x20, 0x20], 0x41);
-        let computed_message = expected_packet.payload[0].downcast_ref::<ShortSalePriceTestStatus>();
-        assert_eq!(&expected_message, computed_message.unwrap());
+        assert_eq!(res.len(), test_header.payload_length as usize + HEADER_LENGTH);
+        let expected_packet = packet_processor.decode_segment(&res).unwrap();
+        let expected_message = ShortSalePriceTestStatus::from(PriceStatus::InEffect, timestamp(), SYMBOL, ShortSaleDetail::ActivatedIntradayPriceDrop);
+        assert_eq!(expected_packet.payload[0], IEXMessage::ShortSalePriceTestStatus(expected_message));
+    }
+
+
+    // Symbol and time of the test messages, the time is `TIMESTAMP` decoded
+    pub(crate) const SYMBOL: Symbol = Symbol::from_bytes([0x5a, 0x49, 0x45, 0x58, 0x54, 0x20, 0x20, 0x20]); // ZIEXT
+
+    pub(crate) fn timestamp() -> DateTime<Utc> {
+        DateTime::<Utc>::from_str("2016-08-23T19:30:32.572715948Z").unwrap()
+    }
+
+    pub(crate) fn make_header(payload_length: u16, message_count: u16) -> IEXHeader {
+        make_protocol_header(IEXProtocol::Tops, payload_length, message_count)
+    }
+
+    fn make_protocol_header(
+        protocol: IEXProtocol,
+        payload_length: u16,
+        message_count: u16,
+    ) -> IEXHeader {
+        IEXHeader {
+            version: 1,
+            __reserved: 0,
+            protocol_id: protocol as u16,
+            channel_id: 1,
+            session_id: 1150681088,
+            payload_length,
+            message_count,
+            stream_offset: 1140157,
+            first_message_seq_number: 37965,
+            send_time: Utc::now(),
+        }
+    }
+
+    pub(crate) const TRADING_STATUS_MESSAGE: [u8; 24] = [
+        0x16, 0x00, 0x48, 0x48, 0xac, 0x63, 0xc0, 0x20, 0x96, 0x86, 0x6d, 0x14, 0x5a, 0x49,
+        0x45, 0x58, 0x54, 0x20, 0x20, 0x20, 0x54, 0x31, 0x20, 0x20,
+    ];
+
+    #[test]
+    fn test_truncated_packet_is_an_error() {
+        let packet_processor: IEXPacketProcessor = IEXPacketProcessor {};
+        let header_bytes = bincode::serialize(&make_header(24, 1)).unwrap();
+        let res: Vec<u8> = [header_bytes, TRADING_STATUS_MESSAGE[..10].to_vec()].concat();
+        let error = packet_processor
+            .decode_segment(&res)
+            .unwrap_err();
+        assert_eq!(error.first_message_seq_number, Some(37965));
+        assert_eq!(error.offset, HEADER_LENGTH);
+        assert_eq!(
+            error.kind,
+            DecodeErrorKind::ShortBuffer {
+                needed: Some(24),
+                available: 10
+            }
+        );
+
+        let error = packet_processor
+            .decode_segment(&res[..20])
+            .unwrap_err();
+        assert_eq!(error.first_message_seq_number, None);
+    }
+
+    #[test]
+    fn test_payload_length_mismatch_is_an_error() {
+        let packet_processor: IEXPacketProcessor = IEXPacketProcessor {};
+        let header_bytes = bincode::serialize(&make_header(30, 1)).unwrap();
+        let res: Vec<u8> = [
+            header_bytes,
+            TRADING_STATUS_MESSAGE.to_vec(),
+            vec![0x00; 6],
+        ]
+        .concat();
+        let error = packet_processor
+            .decode_segment(&res)
+            .unwrap_err();
+        assert_eq!(
+            error.kind,
+            DecodeErrorKind::LengthMismatch {
+                expected: 30,
+                actual: 24
+            }
+        );
+    }
+
+    #[test]
+    fn test_bad_version_is_an_error() {
+        let packet_processor: IEXPacketProcessor = IEXPacketProcessor {};
+        let mut header = make_header(24, 1);
+        header.version = 2;
+        let header_bytes = bincode::serialize(&header).unwrap();
+        let res: Vec<u8> = [header_bytes, TRADING_STATUS_MESSAGE.to_vec()].concat();
+        let error = packet_processor
+            .decode_segment(&res)
+            .unwrap_err();
+        assert_eq!(error.kind, DecodeErrorKind::BadVersion(2));
     }
 
+    #[test]
+    fn test_bad_version_in_frame_is_an_error() {
+        let packet_processor: IEXPacketProcessor = IEXPacketProcessor {};
+        let mut header = make_header(24, 1);
+        header.version = 2;
+        let header_bytes = bincode::serialize(&header).unwrap();
+        let segment: Vec<u8> = [header_bytes, TRADING_STATUS_MESSAGE.to_vec()].concat();
+        let frame = crate::network::tests::make_frame(&segment, false, 0);
+        let error = packet_processor
+            .process_packet_data(Some(PacketData::L2(&frame)))
+            .unwrap_err();
+        assert_eq!(error.kind, DecodeErrorKind::BadVersion(2));
+    }
+
+    #[test]
+    fn test_empty_message_has_no_type() {
+        let packet_processor: IEXPacketProcessor = IEXPacketProcessor {};
+        let header_bytes = bincode::serialize(&make_header(26, 2)).unwrap();
+        let res: Vec<u8> = [header_bytes.clone(), vec![0x00, 0x00], TRADING_STATUS_MESSAGE.to_vec()].concat();
+        let packet = packet_processor.decode_segment(&res).unwrap();
+        assert_eq!(packet.payload[0], IEXMessage::Unknown { msg_type: 0, bytes: vec![] });
+        assert!(matches!(packet.payload[1], IEXMessage::TradingStatus(_)));
+
+        // The byte following an empty message is not taken for its type
+        let header_bytes = bincode::serialize(&make_header(1, 1)).unwrap();
+        let res: Vec<u8> = [header_bytes, vec![0x00, 0x00, 0x48]].concat();
+        let error = packet_processor.decode_segment(&res).unwrap_err();
+        assert_eq!(error.message_index, Some(0));
+        assert_eq!(error.msg_type, None);
+    }
+
+    #[test]
+    fn test_unknown_discriminant_reports_message() {
+        let packet_processor: IEXPacketProcessor = IEXPacketProcessor {};
+        let header_bytes = bincode::serialize(&make_header(48, 2)).unwrap();
+        let mut bad_status = TRADING_STATUS_MESSAGE.to_vec();
+        bad_status[3] = 0x5a;
+        let res: Vec<u8> = [header_bytes, TRADING_STATUS_MESSAGE.to_vec(), bad_status].concat();
+        let error = packet_processor
+            .decode_segment(&res)
+            .unwrap_err();
+        assert_eq!(error.message_index, Some(1));
+        assert_eq!(error.msg_type, Some(0x48));
+        assert_eq!(error.offset, HEADER_LENGTH + 24 + 2);
+        assert!(matches!(
+            error.kind,
+            DecodeErrorKind::UnknownDiscriminant(_)
+        ));
+    }
+
+    #[test]
+    fn test_unknown_message_type_is_skipped() {
+        let packet_processor: IEXPacketProcessor = IEXPacketProcessor {};
+        let header_bytes = bincode::serialize(&make_header(29, 2)).unwrap();
+        let res: Vec<u8> = [
+            header_bytes,
+            vec![0x03, 0x00, 0x7a, 0x01, 0x02],
+            TRADING_STATUS_MESSAGE.to_vec(),
+        ]
+        .concat();
+        let packet = packet_processor
+            .decode_segment(&res)
+            .unwrap();
+        assert_eq!(
+            packet.payload[0],
+            IEXMessage::Unknown {
+                msg_type: 0x7a,
+                bytes: vec![0x7a, 0x01, 0x02]
+            }
+        );
+        assert!(matches!(packet.payload[1], IEXMessage::TradingStatus(_)));
+    }
+
+    const TIMESTAMP: [u8; 8] = [0xac, 0x63, 0xc0, 0x20, 0x96, 0x86, 0x6d, 0x14];
+
+    // Wrap a single message in a packet and decode it
+    fn decode_single_message(message: Vec<u8>) -> IEXMessage {
+        decode_single_protocol_message(IEXProtocol::Tops, message)
+    }
+
+    fn decode_single_protocol_message(protocol: IEXProtocol, message: Vec<u8>) -> IEXMessage {
+        let packet_processor: IEXPacketProcessor = IEXPacketProcessor {};
+        let length = (message.len() as u16).to_le_bytes().to_vec();
+        let header = make_protocol_header(protocol, message.len() as u16 + 2, 1);
+        let header_bytes = bincode::serialize(&header).unwrap();
+        let res: Vec<u8> = [header_bytes, length, message].concat();
+        let mut packet = packet_processor
+            .decode_segment(&res)
+            .unwrap();
+        packet.payload.remove(0)
+    }
 
+    #[test]
+    fn test_can_read_system_event_message() {
+        let message = decode_single_message([vec![0x53, 0x45], TIMESTAMP.to_vec()].concat());
+        match message {
+            IEXMessage::SystemEvent(system_event) => {
+                assert_eq!(system_event.system_event, SystemEvent::EndOfSystemHours);
+                assert_eq!(system_event.timestamp, timestamp());
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_can_read_trade_break_message() {
+        let message = decode_single_message(
+            [
+                vec![0x42, 0x00],
+                TIMESTAMP.to_vec(),
+                SYMBOL.as_bytes().to_vec(),
+                vec![0x64, 0x00, 0x00, 0x00],
+                vec![0x24, 0x1d, 0x0f, 0x00, 0x00, 0x00, 0x00, 0x00],
+                vec![0x96, 0x8f, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ]
+            .concat(),
+        );
+        match message {
+            IEXMessage::TradeBreak(trade_break) => {
+                assert_eq!(trade_break.symbol, SYMBOL);
+                assert_eq!(trade_break.size, 100);
+                assert_eq!(trade_break.price, Price::from_raw(990500));
+                assert_eq!(trade_break.trade_id, 429974);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_can_read_trade_report_flags() {
+        let message = decode_single_message(
+            [
+                vec![0x54, 0xa8],
+                TIMESTAMP.to_vec(),
+                SYMBOL.as_bytes().to_vec(),
+                vec![0x64, 0x00, 0x00, 0x00],
+                vec![0x24, 0x1d, 0x0f, 0x00, 0x00, 0x00, 0x00, 0x00],
+                vec![0x96, 0x8f, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ]
+            .concat(),
+        );
+        match message {
+            IEXMessage::TradeReport(trade) => {
+                let flags = trade.sale_condition_flags;
+                assert!(flags.is_intermarket_sweep());
+                assert!(!flags.is_extended_hours());
+                assert!(flags.is_odd_lot());
+                assert!(!flags.is_trade_through_exempt());
+                assert!(flags.is_single_price_cross());
+                assert_eq!(
+                    format!("{:?}", flags),
+                    "SaleConditionFlags(IntermarketSweep | OddLot | SinglePriceCross)"
+                );
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        assert_eq!(
+            format!("{:?}", SaleConditionFlags::default()),
+            "SaleConditionFlags(empty)"
+        );
+    }
+
+    #[test]
+    fn test_can_read_security_directory_flags() {
+        let message = decode_single_message(
+            [
+                vec![0x44, 0xa0],
+                TIMESTAMP.to_vec(),
+                SYMBOL.as_bytes().to_vec(),
+                vec![0x64, 0x00, 0x00, 0x00],
+                vec![0x24, 0x1d, 0x0f, 0x00, 0x00, 0x00, 0x00, 0x00],
+                vec![0x01],
+            ]
+            .concat(),
+        );
+        match message {
+            IEXMessage::SecurityDirectory(directory) => {
+                assert!(directory.flags.is_test_security());
+                assert!(!directory.flags.is_when_issued());
+                assert!(directory.flags.is_etp());
+                assert_eq!(directory.round_lot_size, 100);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        let quote_flags = QuoteFlags::from_bits(0xc0);
+        assert!(!quote_flags.is_symbol_available());
+        assert!(!quote_flags.is_regular_market_session());
+        assert!(QuoteFlags::default().is_symbol_available());
+    }
+
+    #[test]
+    fn test_can_read_reason_and_detail_codes() {
+        let message = decode_single_message(
+            [vec![0x48, 0x4f], TIMESTAMP.to_vec(), SYMBOL.as_bytes().to_vec(), b"MCB1".to_vec()].concat(),
+        );
+        match message {
+            IEXMessage::TradingStatus(status) => {
+                assert_eq!(status.reason, TradingStatusReason::MarketWideCircuitBreakerLevel1);
+                assert_eq!(status.reason.code(), *b"MCB1");
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        let message = decode_single_message(
+            [vec![0x48, 0x48], TIMESTAMP.to_vec(), SYMBOL.as_bytes().to_vec(), b"XYZ ".to_vec()].concat(),
+        );
+        match message {
+            IEXMessage::TradingStatus(status) => {
+                assert_eq!(status.reason, TradingStatusReason::Other(*b"XYZ "));
+                assert_eq!(status.reason.description(), "Unknown reason");
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        let message = decode_single_message(
+            [vec![0x50, 0x00], TIMESTAMP.to_vec(), SYMBOL.as_bytes().to_vec(), vec![0x43]].concat(),
+        );
+        match message {
+            IEXMessage::ShortSalePriceTestStatus(status) => {
+                assert_eq!(status.detail, ShortSaleDetail::ContinuedFromPriorDay);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        assert_eq!(TradingStatusReason::from(*b"    "), TradingStatusReason::NoReason);
+        assert_eq!(TradingStatusReason::HaltNewsPending.to_string(), "Trading halt: news pending");
+        assert_eq!(ShortSaleDetail::from(b'Z'), ShortSaleDetail::Other(b'Z'));
+        assert_eq!(ShortSaleDetail::Other(b'Z').code(), b'Z');
+        assert_eq!(bincode::serialize(&ShortSaleDetail::Deactivated).unwrap(), b"D");
+    }
+
+    #[test]
+    fn test_can_read_official_price_message() {
+        let message = decode_single_message(
+            [
+                vec![0x58, 0x51],
+                TIMESTAMP.to_vec(),
+                SYMBOL.as_bytes().to_vec(),
+                vec![0x24, 0x1d, 0x0f, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ]
+            .concat(),
+        );
+        match message {
+            IEXMessage::OfficialPrice(official_price) => {
+                assert_eq!(official_price.price_type, OfficialPriceType::OpeningPrice);
+                assert_eq!(official_price.symbol, SYMBOL);
+                assert_eq!(official_price.official_price, Price::from_raw(990500));
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_can_read_operational_halt_message() {
+        let message =
+            decode_single_message([vec![0x4f, 0x4f], TIMESTAMP.to_vec(), SYMBOL.as_bytes().to_vec()].concat());
+        match message {
+            IEXMessage::OperationalHalt(operational_halt) => {
+                assert_eq!(operational_halt.halt_status, OperationalHaltStatus::Halted);
+                assert_eq!(operational_halt.symbol, SYMBOL);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_can_read_retail_liquidity_indicator_message() {
+        let message =
+            decode_single_message([vec![0x49, 0x43], TIMESTAMP.to_vec(), SYMBOL.as_bytes().to_vec()].concat());
+        let expected_message = RetailLiquidityIndicatorMessage::from(
+            RetailLiquidityIndicator::BuySellInterest,
+            timestamp(),
+            SYMBOL,
+        );
+        assert_eq!(message, IEXMessage::RetailLiquidityIndicator(expected_message));
+    }
+
+    #[test]
+    fn test_can_read_price_level_update_messages() {
+        let body = [
+            TIMESTAMP.to_vec(),
+            SYMBOL.as_bytes().to_vec(),
+            vec![0x64, 0x00, 0x00, 0x00],
+            vec![0x24, 0x1d, 0x0f, 0x00, 0x00, 0x00, 0x00, 0x00],
+        ]
+        .concat();
+        let message = decode_single_protocol_message(
+            IEXProtocol::Deep,
+            [vec![0x38, 0x01], body.clone()].concat(),
+        );
+        let expected_message = PriceLevelUpdateMessage::from(
+            Side::Buy,
+            PriceLevelEventFlags::EventProcessingComplete,
+            timestamp(),
+            SYMBOL,
+            100,
+            Price::from_raw(990500),
+        );
+        assert_eq!(message, IEXMessage::PriceLevelUpdateBuy(expected_message));
+
+        let message = decode_single_protocol_message(
+            IEXProtocol::Deep,
+            [vec![0x35, 0x00], body.clone()].concat(),
+        );
+        match message {
+            IEXMessage::PriceLevelUpdateSell(price_level) => {
+                assert_eq!(price_level.side(), Side::Sell);
+                assert!(!price_level.is_event_complete());
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+
+        // Price level updates are not part of TOPS
+        let message = decode_single_message([vec![0x38, 0x01], body].concat());
+        assert!(matches!(message, IEXMessage::Unknown { msg_type: 0x38, .. }));
+    }
+
+    #[test]
+    fn test_can_read_security_event_message() {
+        let message = decode_single_protocol_message(
+            IEXProtocol::Deep,
+            [vec![0x45, 0x43], TIMESTAMP.to_vec(), SYMBOL.as_bytes().to_vec()].concat(),
+        );
+        match message {
+            IEXMessage::SecurityEvent(security_event) => {
+                assert_eq!(
+                    security_event.security_event,
+                    SecurityEvent::ClosingProcessComplete
+                );
+                assert_eq!(security_event.symbol, SYMBOL);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unsupported_protocol_is_an_error() {
+        let packet_processor: IEXPacketProcessor = IEXPacketProcessor {};
+        let mut header = make_header(24, 1);
+        header.protocol_id = 0x1234;
+        let header_bytes = bincode::serialize(&header).unwrap();
+        let res: Vec<u8> = [header_bytes, TRADING_STATUS_MESSAGE.to_vec()].concat();
+        let error = packet_processor
+            .decode_segment(&res)
+            .unwrap_err();
+        assert_eq!(error.kind, DecodeErrorKind::UnsupportedProtocol(0x1234));
+    }
+
+    #[test]
+    fn test_can_read_deep_plus_order_messages() {
+        let timestamp = timestamp();
+        let order_id = vec![0x2a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let size = vec![0x64, 0x00, 0x00, 0x00];
+        let price = vec![0x24, 0x1d, 0x0f, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let trade_id = vec![0x96, 0x8f, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let header = [TIMESTAMP.to_vec(), SYMBOL.as_bytes().to_vec(), order_id].concat();
+
+        let message = decode_single_protocol_message(
+            IEXProtocol::DeepPlus,
+            [vec![0x61, 0x35], header.clone(), size.clone(), price.clone()].concat(),
+        );
+        let expected_message =
+            AddOrderMessage::from(Side::Sell, timestamp, SYMBOL, 42, 100, Price::from_raw(990500));
+        assert_eq!(message, IEXMessage::AddOrder(expected_message));
+
+        let message = decode_single_protocol_message(
+            IEXProtocol::DeepPlus,
+            [vec![0x4d, 0x01], header.clone(), size.clone(), price.clone()].concat(),
+        );
+        let expected_message = OrderModifyMessage::from(0x01, timestamp, SYMBOL, 42, 100, Price::from_raw(990500));
+        assert!(expected_message.maintains_priority());
+        assert_eq!(message, IEXMessage::OrderModify(expected_message));
+
+        let message = decode_single_protocol_message(
+            IEXProtocol::DeepPlus,
+            [vec![0x52, 0x00], header.clone()].concat(),
+        );
+        let expected_message = OrderDeleteMessage::from(timestamp, SYMBOL, 42);
+        assert_eq!(message, IEXMessage::OrderDelete(expected_message));
+
+        let message = decode_single_protocol_message(
+            IEXProtocol::DeepPlus,
+            [vec![0x4c, 0x00], header, size, price, trade_id].concat(),
+        );
+        let expected_message =
+            OrderExecutedMessage::from(SaleConditionFlags::default(), timestamp, SYMBOL, 42, 100, Price::from_raw(990500), 429974);
+        assert_eq!(message, IEXMessage::OrderExecuted(expected_message));
+    }
+
+    #[test]
+    fn test_can_read_packet_from_frame() {
+        let packet_processor: IEXPacketProcessor = IEXPacketProcessor {};
+        let header_bytes = bincode::serialize(&make_header(24, 1)).unwrap();
+        let segment: Vec<u8> = [header_bytes, TRADING_STATUS_MESSAGE.to_vec()].concat();
+
+        for (vlan, ip_options) in [(false, 0), (true, 0), (true, 4)] {
+            let frame = crate::network::tests::make_frame(&segment, vlan, ip_options);
+            let packet = packet_processor
+                .process_packet_data(Some(PacketData::L2(&frame)))
+                .unwrap()
+                .unwrap();
+            assert_eq!(packet.source, Some("10.0.0.1:10378".parse().unwrap()));
+            assert_eq!(packet.destination, Some("233.215.21.4:10378".parse().unwrap()));
+            assert!(matches!(packet.payload[0], IEXMessage::TradingStatus(_)));
+        }
+    }
+
+    #[test]
+    fn test_non_iex_packets_are_skipped() {
+        let packet_processor: IEXPacketProcessor = IEXPacketProcessor {};
+        let frame = crate::network::tests::make_frame(&[0x00; 48], false, 0);
+        let packet = packet_processor
+            .process_packet_data(Some(PacketData::L2(&frame)))
+            .unwrap();
+        assert!(packet.is_none());
+    }
+
+    #[test]
+    fn test_can_read_packet_from_other_link_types() {
+        let packet_processor: IEXPacketProcessor = IEXPacketProcessor {};
+        let header_bytes = bincode::serialize(&make_header(24, 1)).unwrap();
+        let segment: Vec<u8> = [header_bytes, TRADING_STATUS_MESSAGE.to_vec()].concat();
+        let frame = crate::network::tests::make_frame(&segment, false, 0);
+        let ip_packet = &frame[14..];
+        let destination = Some("233.215.21.4:10378".parse().unwrap());
+
+        // Raw IP
+        let packet = packet_processor
+            .process_link_data(ip_packet, Linktype::RAW, ip_packet.len())
+            .unwrap()
+            .unwrap();
+        assert_eq!(packet.destination, destination);
+
+        // Linux cooked capture
+        let sll_header = vec![
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x06, 0x00, 0x1c, 0x73, 0x00, 0x00, 0x99, 0x00, 0x00,
+            0x08, 0x00,
+        ];
+        let sll_frame = [sll_header, ip_packet.to_vec()].concat();
+        let packet = packet_processor
+            .process_link_data(&sll_frame, Linktype::LINUX_SLL, sll_frame.len())
+            .unwrap()
+            .unwrap();
+        assert_eq!(packet.destination, destination);
+
+        // Linux cooked capture v2
+        let sll2_frame = crate::network::tests::make_sll2_frame(&segment);
+        let packet = packet_processor
+            .process_link_data(&sll2_frame, LINKTYPE_LINUX_SLL2, sll2_frame.len())
+            .unwrap()
+            .unwrap();
+        assert_eq!(packet.destination, destination);
+        assert!(matches!(packet.payload[0], IEXMessage::TradingStatus(_)));
+
+        // UDP only: the ports are known, not the addresses
+        let packet = packet_processor
+            .process_packet_data(Some(PacketData::L4(IP_PROTOCOL_UDP, &ip_packet[20..])))
+            .unwrap()
+            .unwrap();
+        assert_eq!(packet.destination, Some("0.0.0.0:10378".parse().unwrap()));
+
+        // IPv6 and unknown link types
+        let packet = packet_processor
+            .process_packet_data(Some(PacketData::L3(0x86dd, ip_packet)))
+            .unwrap();
+        assert!(packet.is_none());
+        assert!(packet_processor
+            .process_link_data(&frame, Linktype(147), frame.len())
+            .is_err());
+    }
 }
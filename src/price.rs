@@ -0,0 +1,270 @@
+use std::error::Error;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+const DECIMALS: usize = 4;
+
+/// Price as sent by IEX: a fixed-point number with 4 decimal digits.
+///
+/// The raw value is the price multiplied by 10000, so $99.05 is `990500`.
+/// Parsing and formatting are exact; on the wire (and with serde) a price is
+/// its raw `i64` value. The arithmetic operators panic on overflow in release
+/// builds too; the `checked_*` methods return `None` instead.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Price(i64);
+
+impl Price {
+    /// Raw units per dollar
+    pub const SCALE: i64 = 10_000;
+    pub const ZERO: Price = Price(0);
+
+    pub const fn from_raw(raw: i64) -> Price {
+        Price(raw)
+    }
+
+    pub const fn raw(self) -> i64 {
+        self.0
+    }
+
+    /// Whole dollars, e.g. `Price::from_dollars(100)` is $100
+    pub fn from_dollars(dollars: i64) -> Price {
+        Price(dollars.checked_mul(Price::SCALE).expect("price overflow"))
+    }
+
+    /// Approximate value, for display or statistics only
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / Price::SCALE as f64
+    }
+
+    pub fn checked_add(self, other: Price) -> Option<Price> {
+        self.0.checked_add(other.0).map(Price)
+    }
+
+    pub fn checked_sub(self, other: Price) -> Option<Price> {
+        self.0.checked_sub(other.0).map(Price)
+    }
+
+    /// The price multiplied by `factor`, e.g. a tick size times a number of ticks
+    pub fn checked_mul(self, factor: i64) -> Option<Price> {
+        self.0.checked_mul(factor).map(Price)
+    }
+
+    /// Value of `quantity` shares in raw units, 1/10000 of a dollar like
+    /// `raw()`. No price and quantity can overflow an `i128`.
+    pub fn notional(self, quantity: u32) -> i128 {
+        self.0 as i128 * quantity as i128
+    }
+}
+
+impl Add for Price {
+    type Output = Price;
+
+    fn add(self, other: Price) -> Price {
+        self.checked_add(other).expect("price overflow")
+    }
+}
+
+impl AddAssign for Price {
+    fn add_assign(&mut self, other: Price) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Price {
+    type Output = Price;
+
+    fn sub(self, other: Price) -> Price {
+        self.checked_sub(other).expect("price overflow")
+    }
+}
+
+impl SubAssign for Price {
+    fn sub_assign(&mut self, other: Price) {
+        *self = *self - other;
+    }
+}
+
+impl Neg for Price {
+    type Output = Price;
+
+    fn neg(self) -> Price {
+        Price(self.0.checked_neg().expect("price overflow"))
+    }
+}
+
+impl Sum for Price {
+    fn sum<I: Iterator<Item = Price>>(iter: I) -> Price {
+        iter.fold(Price::ZERO, Add::add)
+    }
+}
+
+impl fmt::Display for Price {
+    // Trailing zeros of the decimals are dropped unless a precision is given:
+    // 99.05, 100, {:.2} of 100 is 100.00. A precision below 4 rounds half away from zero.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut units = self.0.unsigned_abs();
+        let mut decimals = DECIMALS;
+        if let Some(precision) = f.precision() {
+            if precision < DECIMALS {
+                let divisor = 10u64.pow((DECIMALS - precision) as u32);
+                units = (units + divisor / 2) / divisor;
+                decimals = precision;
+            }
+        }
+        let scale = 10u64.pow(decimals as u32);
+        let (whole, mut fraction) = (units / scale, units % scale);
+        let mut digits = decimals;
+        if f.precision().is_none() {
+            while digits > 0 && fraction % 10 == 0 {
+                fraction /= 10;
+                digits -= 1;
+            }
+        }
+        let mut text = whole.to_string();
+        if digits > 0 {
+            text.push_str(&format!(".{:0width$}", fraction, width = digits));
+        }
+        // Padding for a precision above 4
+        if let Some(precision) = f.precision() {
+            if precision > DECIMALS {
+                text.push_str(&"0".repeat(precision - DECIMALS));
+            }
+        }
+        f.pad_integral(self.0 >= 0 || units == 0, "", &text)
+    }
+}
+
+impl fmt::Debug for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ParsePriceError(String);
+
+impl fmt::Display for ParsePriceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid price {:?}", self.0)
+    }
+}
+
+impl Error for ParsePriceError {}
+
+impl FromStr for Price {
+    type Err = ParsePriceError;
+
+    // Decimal notation with at most 4 decimals, e.g. "99.05", "-0.0001", "100"
+    fn from_str(s: &str) -> Result<Price, ParsePriceError> {
+        let error = || ParsePriceError(s.to_string());
+        let (negative, unsigned) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let all_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if (whole.is_empty() && fraction.is_empty())
+            || fraction.len() > DECIMALS
+            || !all_digits(whole)
+            || !all_digits(fraction)
+        {
+            return Err(error());
+        }
+        let whole: i64 = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().map_err(|_| error())?
+        };
+        let fraction: i64 = format!("{:0<width$}", fraction, width = DECIMALS)
+            .parse()
+            .map_err(|_| error())?;
+        let raw = whole
+            .checked_mul(Price::SCALE)
+            .and_then(|units| units.checked_add(fraction))
+            .ok_or_else(error)?;
+        Ok(Price(if negative { -raw } else { raw }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_price() {
+        assert_eq!("99.05".parse(), Ok(Price::from_raw(990500)));
+        assert_eq!("100".parse(), Ok(Price::from_dollars(100)));
+        assert_eq!("-0.0001".parse(), Ok(Price::from_raw(-1)));
+        assert_eq!(".5".parse(), Ok(Price::from_raw(5000)));
+        assert!("99.00001".parse::<Price>().is_err());
+        assert!("1e4".parse::<Price>().is_err());
+        assert!("".parse::<Price>().is_err());
+        assert!(".".parse::<Price>().is_err());
+        assert!("9999999999999999".parse::<Price>().is_err());
+    }
+
+    #[test]
+    fn test_format_price() {
+        assert_eq!(Price::from_raw(990500).to_string(), "99.05");
+        assert_eq!(Price::from_raw(1_000_000).to_string(), "100");
+        assert_eq!(Price::from_raw(-1).to_string(), "-0.0001");
+        assert_eq!(format!("{:.2}", Price::from_raw(1_000_000)), "100.00");
+        assert_eq!(format!("{:.2}", Price::from_raw(990550)), "99.06");
+        assert_eq!(format!("{:.6}", Price::from_raw(990550)), "99.055000");
+        assert_eq!(format!("{:>8}", Price::from_raw(990500)), "   99.05");
+        for raw in [0, 1, 990500, -123456789, i64::MAX, i64::MIN + 1] {
+            let price = Price::from_raw(raw);
+            assert_eq!(price.to_string().parse(), Ok(price));
+        }
+    }
+
+    #[test]
+    fn test_price_arithmetic_and_ordering() {
+        let bid: Price = "99.05".parse().unwrap();
+        let ask: Price = "99.07".parse().unwrap();
+        assert!(bid < ask);
+        assert_eq!(ask - bid, Price::from_raw(200));
+        assert_eq!(bid.notional(100), 99_050_000);
+        assert_eq!(
+            Price::from_raw(i64::MAX).notional(u32::MAX),
+            i64::MAX as i128 * u32::MAX as i128
+        );
+        assert_eq!(bid.checked_mul(2), Some("198.1".parse().unwrap()));
+        assert_eq!(
+            [bid, ask].into_iter().sum::<Price>(),
+            "198.12".parse().unwrap()
+        );
+        assert_eq!(-bid + bid, Price::ZERO);
+        assert_eq!(Price::from_raw(i64::MAX).checked_add(bid), None);
+        assert_eq!(Price::from_raw(i64::MAX).checked_mul(2), None);
+    }
+
+    #[test]
+    fn test_price_overflow_panics() {
+        let overflows: [fn() -> Price; 2] = [
+            || Price::from_raw(i64::MIN) - Price::from_raw(1),
+            || Price::from_dollars(i64::MAX / Price::SCALE + 1),
+        ];
+        for overflow in overflows {
+            let payload = std::panic::catch_unwind(overflow).unwrap_err();
+            let message = match payload.downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => payload.downcast_ref::<String>().unwrap().clone(),
+            };
+            assert_eq!(message, "price overflow");
+        }
+    }
+
+    #[test]
+    fn test_price_serde_is_raw_value() {
+        let price = Price::from_raw(990500);
+        let bytes = bincode::serialize(&price).unwrap();
+        assert_eq!(bytes, 990500i64.to_le_bytes());
+        assert_eq!(bincode::deserialize::<Price>(&bytes).unwrap(), price);
+    }
+}
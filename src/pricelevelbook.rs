@@ -0,0 +1,276 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::iexdata::*;
+use crate::packetprocessor::IEXPacket;
+
+// Displayed size at a price
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PriceLevel {
+    pub price: Price,
+    pub size: u32,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct PriceLevelSnapshot {
+    // Best (highest) bid first
+    pub bids: Vec<PriceLevel>,
+    // Best (lowest) ask first
+    pub asks: Vec<PriceLevel>,
+}
+
+/// Order book of a single symbol built from DEEP price level updates.
+///
+/// Updates flagged as part of an order book transaction are buffered and only
+/// applied once the update completing the transaction is received, so queries
+/// never observe a half-applied transaction.
+#[derive(Debug, Default)]
+pub struct PriceLevelBook {
+    bids: BTreeMap<Price, u32>,
+    asks: BTreeMap<Price, u32>,
+    pending: Vec<(Side, Price, u32)>,
+}
+
+impl PriceLevelBook {
+    pub fn new() -> PriceLevelBook {
+        PriceLevelBook::default()
+    }
+
+    pub fn apply(&mut self, update: &PriceLevelUpdateMessage) {
+        self.pending.push((update.side(), update.price, update.size));
+        if update.is_event_complete() {
+            for (side, price, size) in std::mem::take(&mut self.pending) {
+                self.set_level(side, price, size);
+            }
+        }
+    }
+
+    fn set_level(&mut self, side: Side, price: Price, size: u32) {
+        let levels = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        if size == 0 {
+            levels.remove(&price);
+        } else {
+            levels.insert(price, size);
+        }
+    }
+
+    // True while updates of an unfinished transaction are buffered
+    pub fn is_in_transaction(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn best_bid(&self) -> Option<PriceLevel> {
+        self.best_bids(1).pop()
+    }
+
+    pub fn best_ask(&self) -> Option<PriceLevel> {
+        self.best_asks(1).pop()
+    }
+
+    pub fn best_bids(&self, n: usize) -> Vec<PriceLevel> {
+        self.bids
+            .iter()
+            .rev()
+            .take(n)
+            .map(|(&price, &size)| PriceLevel { price, size })
+            .collect()
+    }
+
+    pub fn best_asks(&self, n: usize) -> Vec<PriceLevel> {
+        self.asks
+            .iter()
+            .take(n)
+            .map(|(&price, &size)| PriceLevel { price, size })
+            .collect()
+    }
+
+    /// Displayed size at `price`, 0 when there is no level
+    pub fn depth_at(&self, side: Side, price: Price) -> u32 {
+        let levels = match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        };
+        levels.get(&price).copied().unwrap_or(0)
+    }
+
+    pub fn total_displayed_size(&self, side: Side) -> u64 {
+        let levels = match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        };
+        levels.values().map(|&size| size as u64).sum()
+    }
+
+    pub fn snapshot(&self) -> PriceLevelSnapshot {
+        PriceLevelSnapshot {
+            bids: self.best_bids(self.bids.len()),
+            asks: self.best_asks(self.asks.len()),
+        }
+    }
+}
+
+/// Price level books of every symbol of a DEEP feed
+#[derive(Debug, Default)]
+pub struct PriceLevelBooks {
+    books: HashMap<Symbol, PriceLevelBook>,
+}
+
+impl PriceLevelBooks {
+    pub fn new() -> PriceLevelBooks {
+        PriceLevelBooks::default()
+    }
+
+    pub fn process_packet(&mut self, packet: &IEXPacket) {
+        for message in &packet.payload {
+            self.update(message);
+        }
+    }
+
+    pub fn update(&mut self, message: &IEXMessage) {
+        match message {
+            IEXMessage::PriceLevelUpdateBuy(update) | IEXMessage::PriceLevelUpdateSell(update) => {
+                self.books.entry(update.symbol).or_default().apply(update);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn get(&self, symbol: &Symbol) -> Option<&PriceLevelBook> {
+        self.books.get(symbol)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Symbol, &PriceLevelBook)> {
+        self.books.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packetprocessor::tests::{timestamp, SYMBOL};
+
+    fn update(side: Side, complete: bool, size: u32, price: Price) -> PriceLevelUpdateMessage {
+        let flags = if complete {
+            PriceLevelEventFlags::EventProcessingComplete
+        } else {
+            PriceLevelEventFlags::OrderBookProcessingEvent
+        };
+        PriceLevelUpdateMessage::from(
+            side,
+            flags,
+            timestamp(),
+            SYMBOL,
+            size,
+            price,
+        )
+    }
+
+    #[test]
+    fn test_book_levels() {
+        let mut book = PriceLevelBook::new();
+        book.apply(&update(Side::Buy, true, 100, Price::from_raw(990500)));
+        book.apply(&update(Side::Buy, true, 200, Price::from_raw(990400)));
+        book.apply(&update(Side::Buy, true, 300, Price::from_raw(990600)));
+        book.apply(&update(Side::Sell, true, 50, Price::from_raw(990700)));
+        book.apply(&update(Side::Sell, true, 70, Price::from_raw(990800)));
+
+        assert_eq!(
+            book.best_bids(2),
+            vec![
+                PriceLevel {
+                    price: Price::from_raw(990600),
+                    size: 300
+                },
+                PriceLevel {
+                    price: Price::from_raw(990500),
+                    size: 100
+                }
+            ]
+        );
+        assert_eq!(
+            book.best_ask(),
+            Some(PriceLevel {
+                price: Price::from_raw(990700),
+                size: 50
+            })
+        );
+        assert_eq!(book.depth_at(Side::Buy, Price::from_raw(990400)), 200);
+        assert_eq!(book.depth_at(Side::Sell, Price::from_raw(990400)), 0);
+        assert_eq!(book.total_displayed_size(Side::Buy), 600);
+        assert_eq!(book.total_displayed_size(Side::Sell), 120);
+
+        // A zero size removes the level
+        book.apply(&update(Side::Buy, true, 0, Price::from_raw(990600)));
+        assert_eq!(
+            book.best_bid(),
+            Some(PriceLevel {
+                price: Price::from_raw(990500),
+                size: 100
+            })
+        );
+        assert_eq!(book.snapshot().bids.len(), 2);
+        assert_eq!(book.snapshot().asks.len(), 2);
+    }
+
+    #[test]
+    fn test_book_applies_transactions_atomically() {
+        let mut book = PriceLevelBook::new();
+        book.apply(&update(Side::Sell, true, 50, Price::from_raw(990700)));
+
+        book.apply(&update(Side::Sell, false, 0, Price::from_raw(990700)));
+        assert!(book.is_in_transaction());
+        assert_eq!(
+            book.best_ask(),
+            Some(PriceLevel {
+                price: Price::from_raw(990700),
+                size: 50
+            })
+        );
+
+        book.apply(&update(Side::Buy, true, 20, Price::from_raw(990700)));
+        assert!(!book.is_in_transaction());
+        assert_eq!(book.best_ask(), None);
+        assert_eq!(
+            book.best_bid(),
+            Some(PriceLevel {
+                price: Price::from_raw(990700),
+                size: 20
+            })
+        );
+    }
+
+    #[test]
+    fn test_books_per_symbol() {
+        let mut books = PriceLevelBooks::new();
+        books.update(&IEXMessage::PriceLevelUpdateBuy(update(
+            Side::Buy,
+            true,
+            100,
+            Price::from_raw(990500),
+        )));
+        books.update(&IEXMessage::PriceLevelUpdateSell(update(
+            Side::Sell,
+            true,
+            50,
+            Price::from_raw(990700),
+        )));
+        let book = books.get(&SYMBOL).unwrap();
+        assert_eq!(
+            book.best_bid(),
+            Some(PriceLevel {
+                price: Price::from_raw(990500),
+                size: 100
+            })
+        );
+        assert_eq!(
+            book.best_ask(),
+            Some(PriceLevel {
+                price: Price::from_raw(990700),
+                size: 50
+            })
+        );
+        assert!(books.get(&"AAPL".parse().unwrap()).is_none());
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::iexdata::*;
+use crate::packetprocessor::IEXPacket;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RetailInterestSide {
+    Buy,
+    Sell,
+}
+
+// A symbol gained (or lost) retail interest on one side at `timestamp`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct RetailInterestChange {
+    pub timestamp: DateTime<Utc>,
+    pub side: RetailInterestSide,
+    pub gained: bool,
+}
+
+#[derive(Debug)]
+struct SymbolRetailInterest {
+    indicator: RetailLiquidityIndicator,
+    changes: Vec<RetailInterestChange>,
+}
+
+/// Follows the Retail Liquidity Indicator messages and records, for each symbol,
+/// when buy or sell retail interest appeared or disappeared during the day.
+#[derive(Debug, Default)]
+pub struct RetailInterestTracker {
+    symbols: HashMap<Symbol, SymbolRetailInterest>,
+}
+
+impl RetailInterestTracker {
+    pub fn new() -> RetailInterestTracker {
+        RetailInterestTracker::default()
+    }
+
+    // Apply every retail liquidity indicator message of the packet
+    pub fn process_packet(&mut self, packet: &IEXPacket) {
+        for message in &packet.payload {
+            if let IEXMessage::RetailLiquidityIndicator(indicator) = message {
+                self.update(indicator);
+            }
+        }
+    }
+
+    pub fn update(&mut self, message: &RetailLiquidityIndicatorMessage) {
+        let state = self
+            .symbols
+            .entry(message.symbol)
+            .or_insert(SymbolRetailInterest {
+                indicator: RetailLiquidityIndicator::SPACE,
+                changes: vec![],
+            });
+        let previous = state.indicator;
+        let current = message.retail_liquidity_indicator;
+
+        if previous.has_buy_interest() != current.has_buy_interest() {
+            state.changes.push(RetailInterestChange {
+                timestamp: message.timestamp,
+                side: RetailInterestSide::Buy,
+                gained: current.has_buy_interest(),
+            });
+        }
+        if previous.has_sell_interest() != current.has_sell_interest() {
+            state.changes.push(RetailInterestChange {
+                timestamp: message.timestamp,
+                side: RetailInterestSide::Sell,
+                gained: current.has_sell_interest(),
+            });
+        }
+        state.indicator = current;
+    }
+
+    /// Latest indicator received for the symbol
+    pub fn indicator(&self, symbol: &Symbol) -> Option<RetailLiquidityIndicator> {
+        self.symbols.get(symbol).map(|state| state.indicator)
+    }
+
+    /// Changes of retail interest for the symbol, in the order they were received
+    pub fn changes(&self, symbol: &Symbol) -> &[RetailInterestChange] {
+        self.symbols
+            .get(symbol)
+            .map(|state| state.changes.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols.keys()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::packetprocessor::tests::SYMBOL;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::<Utc>::from_str(time).unwrap()
+    }
+
+    #[test]
+    fn test_records_gained_and_lost_interest() {
+        let mut tracker = RetailInterestTracker::new();
+        tracker.update(&RetailLiquidityIndicatorMessage::from(
+            RetailLiquidityIndicator::BuyInterest,
+            at("2016-08-23T14:30:00Z"),
+            SYMBOL,
+        ));
+        tracker.update(&RetailLiquidityIndicatorMessage::from(
+            RetailLiquidityIndicator::SellInterest,
+            at("2016-08-23T15:00:00Z"),
+            SYMBOL,
+        ));
+        tracker.update(&RetailLiquidityIndicatorMessage::from(
+            RetailLiquidityIndicator::SPACE,
+            at("2016-08-23T16:00:00Z"),
+            SYMBOL,
+        ));
+
+        assert_eq!(tracker.indicator(&SYMBOL), Some(RetailLiquidityIndicator::SPACE));
+        assert_eq!(
+            tracker.changes(&SYMBOL),
+            &[
+                RetailInterestChange {
+                    timestamp: at("2016-08-23T14:30:00Z"),
+                    side: RetailInterestSide::Buy,
+                    gained: true
+                },
+                RetailInterestChange {
+                    timestamp: at("2016-08-23T15:00:00Z"),
+                    side: RetailInterestSide::Buy,
+                    gained: false
+                },
+                RetailInterestChange {
+                    timestamp: at("2016-08-23T15:00:00Z"),
+                    side: RetailInterestSide::Sell,
+                    gained: true
+                },
+                RetailInterestChange {
+                    timestamp: at("2016-08-23T16:00:00Z"),
+                    side: RetailInterestSide::Sell,
+                    gained: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repeated_indicator_records_nothing() {
+        let mut tracker = RetailInterestTracker::new();
+        for _ in 0..2 {
+            tracker.update(&RetailLiquidityIndicatorMessage::from(
+                RetailLiquidityIndicator::BuySellInterest,
+                at("2016-08-23T14:30:00Z"),
+                SYMBOL,
+            ));
+        }
+        assert_eq!(tracker.changes(&SYMBOL).len(), 2);
+        assert!(tracker.changes(&"AAPL".parse().unwrap()).is_empty());
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::iexdata::IEXHeader;
+use crate::packetprocessor::IEXPacket;
+
+/// Continuity problem found on an IEX-TP stream
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SequenceEvent {
+    /// Messages in `missing` were never received
+    Gap {
+        channel_id: u32,
+        session_id: u32,
+        missing: Range<u64>,
+    },
+    /// The first `message_count` messages of the packet were received already
+    Duplicate {
+        channel_id: u32,
+        session_id: u32,
+        first_message_seq_number: u64,
+        message_count: u16,
+    },
+    /// The packet fills (part of) a gap reported earlier
+    OutOfOrder {
+        channel_id: u32,
+        session_id: u32,
+        first_message_seq_number: u64,
+        message_count: u16,
+    },
+    /// The channel moved to a new session
+    SessionChange {
+        channel_id: u32,
+        previous_session_id: u32,
+        session_id: u32,
+    },
+    /// The stream offset does not follow the previous packet payload. Only
+    /// checked between contiguous packets since the size of missing messages
+    /// is unknown.
+    StreamOffsetMismatch {
+        channel_id: u32,
+        session_id: u32,
+        expected: u64,
+        received: u64,
+    },
+}
+
+#[derive(Debug)]
+struct StreamState {
+    next_seq_number: u64,
+    next_stream_offset: u64,
+    // Gaps not filled yet by late packets
+    missing: Vec<Range<u64>>,
+}
+
+/// Follows every (channel_id, session_id) stream and reports gaps, duplicates,
+/// out of order packets and session changes from the IEX-TP headers.
+#[derive(Debug, Default)]
+pub struct SequenceTracker {
+    streams: HashMap<(u32, u32), StreamState>,
+    sessions: HashMap<u32, u32>,
+}
+
+impl SequenceTracker {
+    pub fn new() -> SequenceTracker {
+        SequenceTracker::default()
+    }
+
+    pub fn process_packet(&mut self, packet: &IEXPacket) -> Vec<SequenceEvent> {
+        match &packet.header {
+            Some(header) => self.check(header),
+            None => vec![],
+        }
+    }
+
+    pub fn check(&mut self, header: &IEXHeader) -> Vec<SequenceEvent> {
+        let mut events = vec![];
+        let channel_id = header.channel_id;
+        let session_id = header.session_id;
+        let first = header.first_message_seq_number;
+        let count = header.message_count as u64;
+        let next_offset = header.stream_offset + header.payload_length as u64;
+
+        if let Some(previous_session_id) = self.sessions.insert(channel_id, session_id) {
+            if previous_session_id != session_id {
+                events.push(SequenceEvent::SessionChange {
+                    channel_id,
+                    previous_session_id,
+                    session_id,
+                });
+            }
+        }
+
+        let state = match self.streams.get_mut(&(channel_id, session_id)) {
+            Some(state) => state,
+            None => {
+                self.streams.insert(
+                    (channel_id, session_id),
+                    StreamState {
+                        next_seq_number: first + count,
+                        next_stream_offset: next_offset,
+                        missing: vec![],
+                    },
+                );
+                return events;
+            }
+        };
+
+        if first == state.next_seq_number {
+            if header.stream_offset != state.next_stream_offset {
+                events.push(SequenceEvent::StreamOffsetMismatch {
+                    channel_id,
+                    session_id,
+                    expected: state.next_stream_offset,
+                    received: header.stream_offset,
+                });
+            }
+            state.next_seq_number = first + count;
+            state.next_stream_offset = next_offset;
+        } else if first > state.next_seq_number {
+            // The stream offset cannot be checked over the gap
+            let missing = state.next_seq_number..first;
+            state.missing.push(missing.clone());
+            events.push(SequenceEvent::Gap {
+                channel_id,
+                session_id,
+                missing,
+            });
+            state.next_seq_number = first + count;
+            state.next_stream_offset = next_offset;
+        } else {
+            // Only the messages before next_seq_number were seen, or missed,
+            // already; the stream moves on with the rest of the packet
+            let received = first..(first + count).min(state.next_seq_number);
+            let fills_gap = state
+                .missing
+                .iter()
+                .any(|gap| gap.start < received.end && received.start < gap.end);
+            if fills_gap {
+                state.missing = remove_range(&state.missing, &received);
+                events.push(SequenceEvent::OutOfOrder {
+                    channel_id,
+                    session_id,
+                    first_message_seq_number: first,
+                    message_count: header.message_count,
+                });
+            } else {
+                events.push(SequenceEvent::Duplicate {
+                    channel_id,
+                    session_id,
+                    first_message_seq_number: first,
+                    message_count: (received.end - received.start) as u16,
+                });
+            }
+            if first + count > state.next_seq_number {
+                state.next_seq_number = first + count;
+                state.next_stream_offset = next_offset;
+            }
+        }
+        events
+    }
+
+    /// Sequence ranges of the stream still missing
+    pub fn missing(&self, channel_id: u32, session_id: u32) -> &[Range<u64>] {
+        self.streams
+            .get(&(channel_id, session_id))
+            .map(|state| state.missing.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+// Subtract `received` from every range of `missing`
+fn remove_range(missing: &[Range<u64>], received: &Range<u64>) -> Vec<Range<u64>> {
+    let mut remaining = vec![];
+    for gap in missing {
+        if gap.start < received.start {
+            remaining.push(gap.start..gap.end.min(received.start));
+        }
+        if gap.end > received.end {
+            remaining.push(gap.start.max(received.end)..gap.end);
+        }
+    }
+    remaining
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn header(session_id: u32, first: u64, count: u16, offset: u64, length: u16) -> IEXHeader {
+        IEXHeader {
+            version: 1,
+            __reserved: 0,
+            protocol_id: 0x8003,
+            channel_id: 1,
+            session_id,
+            payload_length: length,
+            message_count: count,
+            stream_offset: offset,
+            first_message_seq_number: first,
+            send_time: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_continuous_stream_reports_nothing() {
+        let mut tracker = SequenceTracker::new();
+        assert!(tracker.check(&header(1, 10, 2, 100, 50)).is_empty());
+        assert!(tracker.check(&header(1, 12, 1, 150, 24)).is_empty());
+        // Heartbeats carry no message
+        assert!(tracker.check(&header(1, 13, 0, 174, 0)).is_empty());
+        assert!(tracker.check(&header(1, 13, 1, 174, 24)).is_empty());
+    }
+
+    #[test]
+    fn test_gap_out_of_order_and_duplicate() {
+        let mut tracker = SequenceTracker::new();
+        tracker.check(&header(1, 10, 2, 100, 50));
+        assert_eq!(
+            tracker.check(&header(1, 15, 1, 250, 24)),
+            vec![SequenceEvent::Gap {
+                channel_id: 1,
+                session_id: 1,
+                missing: 12..15
+            }]
+        );
+        assert_eq!(tracker.missing(1, 1).len(), 1);
+        assert_eq!(tracker.missing(1, 1)[0], 12..15);
+
+        assert_eq!(
+            tracker.check(&header(1, 13, 1, 174, 24)),
+            vec![SequenceEvent::OutOfOrder {
+                channel_id: 1,
+                session_id: 1,
+                first_message_seq_number: 13,
+                message_count: 1
+            }]
+        );
+        assert_eq!(tracker.missing(1, 1), &[12..13, 14..15]);
+
+        assert_eq!(
+            tracker.check(&header(1, 10, 2, 100, 50)),
+            vec![SequenceEvent::Duplicate {
+                channel_id: 1,
+                session_id: 1,
+                first_message_seq_number: 10,
+                message_count: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn test_session_change_and_stream_offset() {
+        let mut tracker = SequenceTracker::new();
+        tracker.check(&header(1, 10, 2, 100, 50));
+        assert_eq!(
+            tracker.check(&header(1, 12, 1, 160, 24)),
+            vec![SequenceEvent::StreamOffsetMismatch {
+                channel_id: 1,
+                session_id: 1,
+                expected: 150,
+                received: 160
+            }]
+        );
+        assert_eq!(
+            tracker.check(&header(2, 1, 1, 0, 24)),
+            vec![SequenceEvent::SessionChange {
+                channel_id: 1,
+                previous_session_id: 1,
+                session_id: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn test_partial_overlap_moves_the_stream_on() {
+        let mut tracker = SequenceTracker::new();
+        tracker.check(&header(1, 10, 2, 100, 50));
+        // 10 and 11 again, then the new 12 and 13
+        assert_eq!(
+            tracker.check(&header(1, 10, 4, 100, 98)),
+            vec![SequenceEvent::Duplicate {
+                channel_id: 1,
+                session_id: 1,
+                first_message_seq_number: 10,
+                message_count: 2
+            }]
+        );
+        assert!(tracker.check(&header(1, 14, 1, 198, 24)).is_empty());
+        assert!(tracker.missing(1, 1).is_empty());
+    }
+}
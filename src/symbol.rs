@@ -0,0 +1,166 @@
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+const SYMBOL_LENGTH: usize = 8;
+const PADDING: u8 = b' ';
+
+/// Security symbol: 8 ASCII bytes, right padded with spaces as sent by IEX.
+///
+/// Equality and hashing work on the 8 bytes as a single `u64`: the map hasher
+/// sees one word per symbol. Display and comparison with `&str` ignore the padding.
+#[derive(Clone, Copy, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Symbol([u8; SYMBOL_LENGTH]);
+
+impl Symbol {
+    /// Symbol from the raw (padded) bytes of a message
+    pub const fn from_bytes(bytes: [u8; SYMBOL_LENGTH]) -> Symbol {
+        Symbol(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; SYMBOL_LENGTH] {
+        &self.0
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        u64::from_le_bytes(self.0)
+    }
+
+    /// Bytes without the padding
+    pub fn trimmed(&self) -> &[u8] {
+        let end = self
+            .0
+            .iter()
+            .rposition(|&b| b != PADDING)
+            .map_or(0, |last| last + 1);
+        &self.0[..end]
+    }
+
+    /// The symbol without padding, `None` if the bytes are not valid UTF-8
+    pub fn as_str(&self) -> Option<&str> {
+        std::str::from_utf8(self.trimmed()).ok()
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Symbol) -> bool {
+        self.as_u64() == other.as_u64()
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.as_u64());
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.trimmed() == other.as_bytes()
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl From<[u8; SYMBOL_LENGTH]> for Symbol {
+    fn from(bytes: [u8; SYMBOL_LENGTH]) -> Symbol {
+        Symbol(bytes)
+    }
+}
+
+impl fmt::Display for Symbol {
+    // Invalid UTF-8 is replaced rather than failing
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.as_str() {
+            Some(symbol) => f.pad(symbol),
+            None => f.pad(&String::from_utf8_lossy(self.trimmed())),
+        }
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.to_string())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ParseSymbolError(String);
+
+impl fmt::Display for ParseSymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid symbol {:?}", self.0)
+    }
+}
+
+impl Error for ParseSymbolError {}
+
+impl FromStr for Symbol {
+    type Err = ParseSymbolError;
+
+    // 1 to 8 printable ASCII characters, padded with spaces
+    fn from_str(s: &str) -> Result<Symbol, ParseSymbolError> {
+        if s.is_empty() || s.len() > SYMBOL_LENGTH || !s.bytes().all(|b| b.is_ascii_graphic()) {
+            return Err(ParseSymbolError(s.to_string()));
+        }
+        let mut bytes = [PADDING; SYMBOL_LENGTH];
+        bytes[..s.len()].copy_from_slice(s.as_bytes());
+        Ok(Symbol(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display() {
+        let symbol: Symbol = "ZIEXT".parse().unwrap();
+        assert_eq!(symbol.as_bytes(), b"ZIEXT   ");
+        assert_eq!(symbol.to_string(), "ZIEXT");
+        assert_eq!(format!("{:<6}|", symbol), "ZIEXT |");
+        assert_eq!(format!("{:?}", symbol), "\"ZIEXT\"");
+        assert_eq!("BRK.B".parse::<Symbol>().unwrap(), "BRK.B");
+        assert!("".parse::<Symbol>().is_err());
+        assert!("TOOLONGXX".parse::<Symbol>().is_err());
+        assert!("A B".parse::<Symbol>().is_err());
+    }
+
+    #[test]
+    fn test_compare_with_str() {
+        let symbol = Symbol::from_bytes(*b"AAPL    ");
+        assert_eq!(symbol, "AAPL");
+        assert!(symbol != "AAP");
+        assert!(symbol != "AAPL ");
+        assert_eq!(symbol, "AAPL".parse::<Symbol>().unwrap());
+        assert!(symbol < "MSFT".parse::<Symbol>().unwrap());
+    }
+
+    #[test]
+    fn test_invalid_utf8_does_not_panic() {
+        let symbol = Symbol::from_bytes([0xff, b'A', 0, 0x20, 0x20, 0x20, 0x20, 0x20]);
+        assert_eq!(symbol.as_str(), None);
+        assert_eq!(symbol.to_string(), "\u{fffd}A\u{0}");
+    }
+
+    #[test]
+    fn test_symbol_as_map_key() {
+        let mut map = HashMap::new();
+        map.insert(Symbol::from_bytes(*b"ZIEXT   "), 1);
+        assert_eq!(map.get(&"ZIEXT".parse().unwrap()), Some(&1));
+        let bytes = bincode::serialize(&Symbol::from_bytes(*b"ZIEXT   ")).unwrap();
+        assert_eq!(bytes, b"ZIEXT   ");
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::iexdata::*;
+use crate::packetprocessor::IEXPacket;
+
+// Latest TOPS state of a single symbol
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SymbolTopOfBook {
+    pub bid_price: Price,
+    pub bid_size: u32,
+    pub ask_price: Price,
+    pub ask_size: u32,
+    pub last_quote_time: Option<DateTime<Utc>>,
+    pub trading_status: Option<TradingStatus>,
+    pub short_sale_status: Option<PriceStatus>,
+}
+
+/// Per-symbol top of book built from TOPS quote updates, trading status and
+/// short sale price test messages.
+#[derive(Debug, Default)]
+pub struct TopOfBook {
+    symbols: HashMap<Symbol, SymbolTopOfBook>,
+}
+
+impl TopOfBook {
+    pub fn new() -> TopOfBook {
+        TopOfBook::default()
+    }
+
+    pub fn process_packet(&mut self, packet: &IEXPacket) {
+        for message in &packet.payload {
+            self.update(message);
+        }
+    }
+
+    // Apply a single message, ignoring the ones that do not touch the top of book
+    pub fn update(&mut self, message: &IEXMessage) {
+        match message {
+            IEXMessage::QuoteUpdate(quote) => {
+                let entry = self.symbols.entry(quote.symbol).or_default();
+                entry.bid_price = quote.bid_price;
+                entry.bid_size = quote.bid_size;
+                entry.ask_price = quote.ask_price;
+                entry.ask_size = quote.ask_size;
+                entry.last_quote_time = Some(quote.timestamp);
+            }
+            IEXMessage::TradingStatus(trading_status) => {
+                let entry = self.symbols.entry(trading_status.symbol).or_default();
+                entry.trading_status = Some(trading_status.trading_status);
+            }
+            IEXMessage::ShortSalePriceTestStatus(short_sale) => {
+                let entry = self.symbols.entry(short_sale.symbol).or_default();
+                entry.short_sale_status = Some(short_sale.price_status);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn get(&self, symbol: &Symbol) -> Option<&SymbolTopOfBook> {
+        self.symbols.get(symbol)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Symbol, &SymbolTopOfBook)> {
+        self.symbols.iter()
+    }
+
+    /// Copy of the current state of every symbol, sorted by symbol
+    pub fn snapshot(&self) -> Vec<(Symbol, SymbolTopOfBook)> {
+        let mut snapshot: Vec<(Symbol, SymbolTopOfBook)> = self
+            .symbols
+            .iter()
+            .map(|(symbol, top)| (*symbol, top.clone()))
+            .collect();
+        snapshot.sort_by_key(|(symbol, _)| *symbol);
+        snapshot
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packetprocessor::tests::{timestamp, SYMBOL};
+
+    fn quote(symbol: Symbol, bid_size: u32, bid_price: &str, ask_price: &str, ask_size: u32) -> IEXMessage {
+        IEXMessage::QuoteUpdate(QuoteUpdateMessage::from(
+            QuoteFlags::default(), timestamp(), symbol, bid_size, bid_price.parse().unwrap(), ask_price.parse().unwrap(), ask_size,
+        ))
+    }
+
+    fn trading_status(trading_status: TradingStatus, reason: TradingStatusReason) -> IEXMessage {
+        IEXMessage::TradingStatus(TradingStatusMessage {
+            __t: 0x48,
+            trading_status,
+            timestamp: timestamp(),
+            symbol: SYMBOL,
+            reason,
+        })
+    }
+
+    #[test]
+    fn test_top_of_book_follows_quotes_and_status() {
+        let timestamp = timestamp();
+        let mut book = TopOfBook::new();
+        assert!(book.is_empty());
+
+        book.update(&IEXMessage::QuoteUpdate(QuoteUpdateMessage::from(
+            QuoteFlags::default(), timestamp, SYMBOL, 9700, "99.05".parse().unwrap(), "99.07".parse().unwrap(), 1000,
+        )));
+        book.update(&IEXMessage::ShortSalePriceTestStatus(
+            ShortSalePriceTestStatus::from(PriceStatus::InEffect, timestamp, SYMBOL, ShortSaleDetail::ActivatedIntradayPriceDrop),
+        ));
+        book.update(&IEXMessage::TradingStatus(TradingStatusMessage {
+            __t: 0x48,
+            trading_status: TradingStatus::Halt,
+            timestamp,
+            symbol: SYMBOL,
+            reason: TradingStatusReason::HaltNewsPending,
+        }));
+
+        let top = book.get(&SYMBOL).unwrap();
+        assert_eq!(top.bid_price, Price::from_raw(990500));
+        assert_eq!(top.bid_size, 9700);
+        assert_eq!(top.ask_price, Price::from_raw(990700));
+        assert_eq!(top.ask_size, 1000);
+        assert_eq!(top.last_quote_time, Some(timestamp));
+        assert_eq!(top.trading_status, Some(TradingStatus::Halt));
+        assert_eq!(top.short_sale_status, Some(PriceStatus::InEffect));
+
+        let snapshot = book.snapshot();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(&snapshot[0].1, top);
+        assert_eq!(book.iter().count(), 1);
+    }
+
+    #[test]
+    fn test_halt_and_resumption() {
+        let mut book = TopOfBook::new();
+        // A status can come before the first quote of a symbol
+        book.update(&trading_status(TradingStatus::Halt, TradingStatusReason::HaltNewsPending));
+        let top = book.get(&SYMBOL).unwrap();
+        assert_eq!(top.trading_status, Some(TradingStatus::Halt));
+        assert_eq!(top.bid_size, 0);
+        assert_eq!(top.last_quote_time, None);
+
+        book.update(&quote(SYMBOL, 9700, "99.05", "99.07", 1000));
+        book.update(&trading_status(TradingStatus::Trading, TradingStatusReason::NoReason));
+        let top = book.get(&SYMBOL).unwrap();
+        assert_eq!(top.trading_status, Some(TradingStatus::Trading));
+        // The quote is kept across status changes
+        assert_eq!(top.bid_size, 9700);
+        assert_eq!(top.ask_size, 1000);
+    }
+
+    #[test]
+    fn test_quotes_on_unknown_symbols() {
+        let mut book = TopOfBook::new();
+        let other: Symbol = "AAPL".parse().unwrap();
+        book.update(&quote(SYMBOL, 9700, "99.05", "99.07", 1000));
+        assert!(book.get(&other).is_none());
+
+        book.update(&quote(other, 100, "150.01", "150.02", 200));
+        assert_eq!(book.len(), 2);
+        assert_eq!(book.get(&other).unwrap().bid_price, Price::from_raw(1500100));
+        assert_eq!(book.get(&SYMBOL).unwrap().bid_price, Price::from_raw(990500));
+        let symbols: Vec<Symbol> = book.snapshot().iter().map(|(symbol, _)| *symbol).collect();
+        assert_eq!(symbols, vec![other, SYMBOL]);
+    }
+
+    #[test]
+    fn test_zero_size_side_clears_the_book() {
+        let mut book = TopOfBook::new();
+        book.update(&quote(SYMBOL, 9700, "99.05", "99.07", 1000));
+        // No bid left: the side is sent with a zero size and price
+        book.update(&quote(SYMBOL, 0, "0", "99.07", 1000));
+        let top = book.get(&SYMBOL).unwrap();
+        assert_eq!(top.bid_size, 0);
+        assert_eq!(top.bid_price, Price::from_raw(0));
+        assert_eq!(top.ask_price, Price::from_raw(990700));
+        assert_eq!(top.ask_size, 1000);
+
+        book.update(&quote(SYMBOL, 0, "0", "0", 0));
+        let top = book.get(&SYMBOL).unwrap();
+        assert_eq!((top.ask_price, top.ask_size), (Price::from_raw(0), 0));
+    }
+}